@@ -1,92 +1,923 @@
-use clap::Parser;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, anyhow, bail};
+use clap::{Parser, ValueEnum};
+use iot_log_parser::parse_envelope;
+use serde::Serialize;
+use serde_json::Value;
+use tabwriter::TabWriter;
+
+// 掩码最多 65536 位, 防止超大的位号或宽度耗尽内存
+const MAX_BITS: usize = 1 << 16;
+
+// 位号的编号方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BitOrder {
+    // 从最低位开始
+    Lsb,
+    // 从最高位开始, 依赖掩码宽度
+    Msb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
 
 #[derive(Debug, Parser)]
-struct Inclusion {
-    #[arg(long)]
-    hex: String,
+#[command(name = "inclusion")]
+#[command(
+    version,
+    about = "Decode hex bitmasks of any width into set bits and signal names"
+)]
+struct Cli {
+    /// Hex masks such as 0x1FD3C008, or signal names and bit indices with --encode
+    values: Vec<String>,
+
+    /// Same as a positional value, kept for scripts written against the original tool
+    #[arg(long, value_name = "HEX")]
+    hex: Vec<String>,
+
+    /// Read one value per line from this file, `-` for stdin
+    #[arg(short, long, value_name = "PATH")]
+    input: Option<PathBuf>,
+
+    /// Count bits from the most or the least significant bit
+    #[arg(long, value_enum, default_value_t = BitOrder::Msb)]
+    order: BitOrder,
+
+    /// Index of the first bit
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=1))]
+    base: u8,
+
+    /// Mask width in bits, 4 bits per hex digit by default, at most 65536
+    #[arg(short, long, value_name = "BITS", value_parser = parse_width)]
+    width: Option<usize>,
+
+    /// Bit to signal name mapping, one `index name` per line
+    #[arg(short, long, value_name = "PATH")]
+    map: Option<PathBuf>,
+
+    /// Encode signal names or bit indices into a hex mask instead of decoding
+    #[arg(short, long)]
+    encode: bool,
+
+    /// Report bits set or cleared between consecutive values, one `[time] hex` per line
+    #[arg(short, long, conflicts_with = "encode")]
+    changes: bool,
+
+    /// Take each value from this JSON field of IoT gateway log lines
+    #[arg(long, value_name = "NAME", requires = "changes")]
+    field: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+fn parse_width(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(width) if (1..=MAX_BITS).contains(&width) => Ok(width),
+        _ => Err(format!("expected a width between 1 and {MAX_BITS}")),
+    }
+}
+
+// 任意宽度的掩码: 按十六进制位逐个展开, 不经过整数转换, 不会溢出
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mask {
+    // bits[0] 为最低位
+    bits: Vec<bool>,
 }
 
-impl Inclusion {
-    fn to_binary(c: char) -> &'static str {
-        match c {
-            '0' => "0000",
-            '1' => "0001",
-            '2' => "0010",
-            '3' => "0011",
-            '4' => "0100",
-            '5' => "0101",
-            '6' => "0110",
-            '7' => "0111",
-            '8' => "1000",
-            '9' => "1001",
-            'A' | 'a' => "1010",
-            'B' | 'b' => "1011",
-            'C' | 'c' => "1100",
-            'D' | 'd' => "1101",
-            'E' | 'e' => "1110",
-            'F' | 'f' => "1111",
-            _ => "",
+impl Mask {
+    // 可带 0x 前缀, 允许用 `_` 分隔, 宽度为十六进制位数 * 4
+    fn from_hex(hex: &str) -> anyhow::Result<Self> {
+        let digits = hex.trim();
+        let digits = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+            .unwrap_or(digits);
+        let mut bits = Vec::with_capacity(digits.len() * 4);
+        for c in digits.chars().rev().filter(|&c| c != '_') {
+            let Some(digit) = c.to_digit(16) else {
+                bail!("invalid hex digit {c:?} in {hex:?}");
+            };
+            bits.extend((0..4).map(|i| digit >> i & 1 == 1));
+        }
+        if bits.is_empty() {
+            bail!("empty mask {hex:?}");
+        }
+        if bits.len() > MAX_BITS {
+            bail!("mask of {} bits exceeds {MAX_BITS} bits", bits.len());
+        }
+        Ok(Mask { bits })
+    }
+
+    fn from_positions(width: usize, positions: &[usize]) -> Self {
+        let mut bits = vec![false; width];
+        for &position in positions {
+            bits[position] = true;
+        }
+        Mask { bits }
+    }
+
+    // 扩展或收缩到 width 位, 有置位的高位不能被截掉
+    fn with_width(mut self, width: usize) -> anyhow::Result<Self> {
+        if width > MAX_BITS {
+            bail!("width {width} exceeds {MAX_BITS} bits");
         }
+        if self.significant() > width {
+            bail!(
+                "{} has {} significant bits, wider than {width}",
+                self.to_hex(),
+                self.significant()
+            );
+        }
+        self.bits.resize(width, false);
+        Ok(self)
+    }
+
+    fn width(&self) -> usize {
+        self.bits.len()
+    }
+
+    // 去掉前导 0 后的位数
+    fn significant(&self) -> usize {
+        self.bits
+            .iter()
+            .rposition(|&bit| bit)
+            .map_or(0, |at| at + 1)
+    }
+
+    fn count_ones(&self) -> usize {
+        self.bits.iter().filter(|&&bit| bit).count()
     }
 
+    // 二进制字符串, 不含前导 0
     fn binary(&self) -> String {
-        self.hex[2..]
-            .chars()
-            .map(Self::to_binary)
-            .collect::<String>()
-            .trim_start_matches("0")
-            .to_string()
+        match self.significant() {
+            0 => "0".into(),
+            n => self.bits[..n]
+                .iter()
+                .rev()
+                .map(|&bit| if bit { '1' } else { '0' })
+                .collect(),
+        }
     }
-    fn hex_to_binary(&self) -> String {
-        let binary_string =
-            if let Ok(hex_value) = usize::from_str_radix(self.hex.trim_start_matches("0x"), 16) {
-                let binary_string = format!("{:0b}", hex_value);
-                binary_string
-            } else {
-                "".into()
-            };
-        binary_string
+
+    // 大写十六进制, 按宽度补齐
+    fn to_hex(&self) -> String {
+        let digits: String = self
+            .bits
+            .chunks(4)
+            .rev()
+            .map(|nibble| {
+                let digit = nibble
+                    .iter()
+                    .enumerate()
+                    .fold(0, |digit, (i, &bit)| digit | u32::from(bit) << i);
+                char::from_digit(digit, 16)
+                    .unwrap_or('0')
+                    .to_ascii_uppercase()
+            })
+            .collect();
+        format!("0x{digits}")
     }
 
-    // count how many ones in binary string
-    fn count_ones(&self) -> u16 {
-        let binary_string = self.binary();
-        binary_string
-            .as_str()
-            .chars()
-            .fold(0, |ones, x| if x == '1' { ones + 1 } else { ones })
+    // bits 中的下标 -> 对外的位号
+    fn index(&self, position: usize, order: BitOrder, base: usize) -> usize {
+        match order {
+            BitOrder::Lsb => position + base,
+            BitOrder::Msb => self.width() - 1 - position + base,
+        }
+    }
+
+    fn ones(&self) -> Vec<usize> {
+        (0..self.width())
+            .filter(|&position| self.bits[position])
+            .collect()
     }
 
-    fn idx_of_one(&self) -> Vec<(usize, char)> {
-        let binary_string = self.binary();
-        let length = binary_string.len();
-        (0..length)
-            .zip(binary_string.as_str().chars())
-            .filter_map(|(idx, value)| {
-                if value == '1' {
-                    Some((idx + 1, value))
-                } else {
-                    None
+    // 置位的位号, 升序
+    #[cfg(test)]
+    fn set_bits(&self, order: BitOrder, base: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .ones()
+            .into_iter()
+            .map(|position| self.index(position, order, base))
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    // 相对 previous 新置位和被清除的位 (bits 中的下标), 两者宽度需相同
+    fn diff(&self, previous: &Mask) -> (Vec<usize>, Vec<usize>) {
+        let changed =
+            || (0..self.width()).filter(|&position| self.bits[position] != previous.bits[position]);
+        (
+            changed().filter(|&position| self.bits[position]).collect(),
+            changed()
+                .filter(|&position| previous.bits[position])
+                .collect(),
+        )
+    }
+}
+
+// 位号 -> 信号名
+#[derive(Debug, Default)]
+struct Mapping {
+    names: BTreeMap<usize, String>,
+}
+
+impl Mapping {
+    // 每行 `位号 信号名`, 信号名可以包含空格, `#` 开头为注释
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut mapping = Mapping::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (index, name) = line
+                .split_once(char::is_whitespace)
+                .with_context(|| format!("line {}: expected `index name`", n + 1))?;
+            let index: usize = index
+                .parse()
+                .with_context(|| format!("line {}: invalid bit index {index:?}", n + 1))?;
+            let name = name.trim();
+            if mapping.index(name).is_some() {
+                bail!("line {}: duplicate signal {name:?}", n + 1);
+            }
+            if mapping.names.insert(index, name.to_string()).is_some() {
+                bail!("line {}: duplicate bit {index}", n + 1);
+            }
+        }
+        Ok(mapping)
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Mapping::parse(&text).with_context(|| path.display().to_string())
+    }
+
+    fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(&index).map(String::as_str)
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .find_map(|(&index, signal)| (signal == name).then_some(index))
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Signal {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+// 值的来源: 第几个命令行参数, 或 --input 中的第几行 (空行和注释也计数)
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Origin {
+    Arg(usize),
+    Line(usize),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Arg(n) => write!(f, "arg {n}"),
+            Origin::Line(n) => write!(f, "line {n}"),
+        }
+    }
+}
+
+// 时间序列中的一个值, 没有时间时只有来源
+#[derive(Debug)]
+struct Sample {
+    origin: Origin,
+    time: Option<String>,
+    mask: Mask,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Change {
+    #[serde(flatten)]
+    origin: Origin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    value: String,
+    set: Vec<Signal>,
+    cleared: Vec<Signal>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Toggles {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    set: usize,
+    cleared: usize,
+}
+
+// 第一个值作为初始状态, 其置位记为 set 但不计入翻转次数
+#[derive(Debug, Serialize, PartialEq)]
+struct Timeline {
+    changes: Vec<Change>,
+    toggles: Vec<Toggles>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Decoded {
+    value: String,
+    binary: String,
+    width: usize,
+    ones: usize,
+    bits: Vec<Signal>,
+}
+
+impl Cli {
+    fn base(&self) -> usize {
+        usize::from(self.base)
+    }
+
+    // 指定 --width 时补齐到该宽度, 比它宽的值是错误
+    fn fit(&self, mask: Mask) -> anyhow::Result<Mask> {
+        match self.width {
+            Some(width) => mask.with_width(width),
+            None => Ok(mask),
+        }
+    }
+
+    fn decode(&self, value: &str, mapping: &Mapping) -> anyhow::Result<Decoded> {
+        let mask = self.fit(Mask::from_hex(value)?)?;
+        Ok(Decoded {
+            value: mask.to_hex(),
+            binary: mask.binary(),
+            width: mask.width(),
+            ones: mask.count_ones(),
+            bits: self.signals(&mask, &mask.ones(), mapping),
+        })
+    }
+
+    // bits 中的下标 -> 按位号升序的信号
+    fn signals(&self, mask: &Mask, positions: &[usize], mapping: &Mapping) -> Vec<Signal> {
+        let mut signals: Vec<Signal> = positions
+            .iter()
+            .map(|&position| {
+                let index = mask.index(position, self.order, self.base());
+                Signal {
+                    index,
+                    name: mapping.name(index).map(str::to_string),
                 }
             })
-            .collect::<Vec<(usize, char)>>()
+            .collect();
+        signals.sort_unstable_by_key(|signal| signal.index);
+        signals
+    }
+
+    // 信号名或位号 -> 掩码, 未指定宽度时取能容纳最大位号的最小十六进制宽度
+    fn encode(&self, tokens: &[&str], mapping: &Mapping) -> anyhow::Result<Decoded> {
+        let mut offsets = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let index = match mapping.index(token) {
+                Some(index) => index,
+                None => token
+                    .parse()
+                    .with_context(|| format!("unknown signal {token:?}"))?,
+            };
+            let Some(offset) = index.checked_sub(self.base()) else {
+                bail!("bit {index} is below base {}", self.base);
+            };
+            if offset >= MAX_BITS {
+                bail!("bit {index} exceeds {MAX_BITS} bits");
+            }
+            offsets.push(offset);
+        }
+
+        // offset 小于 MAX_BITS, 加一不会溢出
+        let needed = offsets.iter().max().map_or(1, |max| max + 1);
+        let width = self.width.unwrap_or(needed.div_ceil(4) * 4);
+        if needed > width {
+            bail!(
+                "bit {} does not fit in {width} bits",
+                needed - 1 + self.base()
+            );
+        }
+        let positions: Vec<usize> = offsets
+            .iter()
+            .map(|&offset| match self.order {
+                BitOrder::Lsb => offset,
+                BitOrder::Msb => width - 1 - offset,
+            })
+            .collect();
+        let mask = Mask::from_positions(width, &positions);
+        self.decode(&mask.to_hex(), mapping)
     }
+
+    // `[时间] 十六进制`, 或指定 --field 时从 IoT 日志荷载中取该字段 (字符串或整数)
+    fn sample(&self, origin: Origin, input: &str) -> anyhow::Result<Sample> {
+        let Some(field) = &self.field else {
+            let (time, hex) = match input.trim().rsplit_once(char::is_whitespace) {
+                Some((time, hex)) => (Some(time.trim().to_string()), hex),
+                None => (None, input),
+            };
+            let mask = self.fit(Mask::from_hex(hex)?)?;
+            return Ok(Sample { origin, time, mask });
+        };
+
+        let (_, envelope) = parse_envelope(input).map_err(|_| anyhow!("invalid IoT log line"))?;
+        let payload: Value = serde_json::from_str(envelope.payload)?;
+        let mask = match find_field(&payload, field) {
+            Some(Value::String(hex)) => Mask::from_hex(hex)?,
+            Some(Value::Number(number)) => {
+                let number = number
+                    .as_u64()
+                    .with_context(|| format!("{field} = {number} is not a mask"))?;
+                Mask::from_hex(&format!("{number:x}"))?
+            }
+            Some(value) => bail!("{field} = {value} is not a mask"),
+            None => bail!("no field {field:?}"),
+        };
+        Ok(Sample {
+            origin,
+            time: Some(
+                envelope
+                    .server_time
+                    .format("%Y-%m-%d %H:%M:%S%.3f")
+                    .to_string(),
+            ),
+            mask: self.fit(mask)?,
+        })
+    }
+
+    // 所有值统一到 --width 或最宽的值, 保证按最高位编号时位号一致
+    fn timeline(&self, samples: Vec<Sample>, mapping: &Mapping) -> anyhow::Result<Timeline> {
+        let width = match self.width {
+            Some(width) => width,
+            None => samples
+                .iter()
+                .map(|sample| sample.mask.width())
+                .max()
+                .unwrap_or(0),
+        };
+        let mut changes = Vec::new();
+        let mut toggles: BTreeMap<usize, Toggles> = BTreeMap::new();
+        let mut previous: Option<Mask> = None;
+        for sample in samples {
+            let mask = sample.mask.with_width(width)?;
+            let (set, cleared) = match &previous {
+                Some(previous) => mask.diff(previous),
+                None => (mask.ones(), Vec::new()),
+            };
+            let set = self.signals(&mask, &set, mapping);
+            let cleared = self.signals(&mask, &cleared, mapping);
+            if previous.is_some() {
+                if set.is_empty() && cleared.is_empty() {
+                    continue;
+                }
+                for (signal, is_set) in set
+                    .iter()
+                    .map(|s| (s, true))
+                    .chain(cleared.iter().map(|s| (s, false)))
+                {
+                    let entry = toggles.entry(signal.index).or_insert_with(|| Toggles {
+                        index: signal.index,
+                        name: signal.name.clone(),
+                        set: 0,
+                        cleared: 0,
+                    });
+                    if is_set {
+                        entry.set += 1;
+                    } else {
+                        entry.cleared += 1;
+                    }
+                }
+            }
+            changes.push(Change {
+                origin: sample.origin,
+                time: sample.time,
+                value: mask.to_hex(),
+                set,
+                cleared,
+            });
+            previous = Some(mask);
+        }
+        Ok(Timeline {
+            changes,
+            toggles: toggles.into_values().collect(),
+        })
+    }
+
+    // 命令行参数 (含 --hex) 和 --input 的每一行, 带上参数序号或文件中的行号;
+    // 编码时命令行参数合成一个掩码, 文件每行一个, 用 `,` 分隔
+    fn batches(&self) -> anyhow::Result<Vec<(Origin, String)>> {
+        let mut batches = Vec::new();
+        let values: Vec<&str> = self
+            .values
+            .iter()
+            .chain(&self.hex)
+            .map(String::as_str)
+            .collect();
+        if !values.is_empty() {
+            if self.encode {
+                batches.push((Origin::Arg(1), values.join(",")));
+            } else {
+                batches.extend(
+                    values
+                        .iter()
+                        .enumerate()
+                        .map(|(n, value)| (Origin::Arg(n + 1), value.to_string())),
+                );
+            }
+        }
+        if let Some(path) = &self.input {
+            let mut text = String::new();
+            if path.as_os_str() == "-" {
+                io::stdin().read_to_string(&mut text)?;
+            } else {
+                text = fs::read_to_string(path).with_context(|| path.display().to_string())?;
+            }
+            batches.extend(
+                text.lines()
+                    .map(str::trim)
+                    .enumerate()
+                    .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+                    .map(|(n, line)| (Origin::Line(n + 1), line.to_string())),
+            );
+        }
+        Ok(batches)
+    }
+}
+
+// 在荷载中查找第一个名为 field 的键, 包括嵌套的对象和数组
+fn find_field<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map
+            .get(field)
+            .or_else(|| map.values().find_map(|value| find_field(value, field))),
+        Value::Array(values) => values.iter().find_map(|value| find_field(value, field)),
+        _ => None,
+    }
+}
+
+// 例如 `3:过温`, 没有映射时只有位号
+fn format_signals(signals: &[Signal], prefix: &str) -> Vec<String> {
+    signals
+        .iter()
+        .map(|signal| match &signal.name {
+            Some(name) => format!("{prefix}{}:{name}", signal.index),
+            None => format!("{prefix}{}", signal.index),
+        })
+        .collect()
+}
+
+fn write_text(decoded: &Decoded, w: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        w,
+        "{}\t{}",
+        decoded.value,
+        format_signals(&decoded.bits, "").join(" ")
+    )
+}
+
+// 用 `\t` 分隔, 由调用方对齐; 变化行中 `+` 为置位, `-` 为清除
+fn write_timeline(timeline: &Timeline, w: &mut impl Write) -> io::Result<()> {
+    for change in &timeline.changes {
+        let mut bits = format_signals(&change.set, "+");
+        bits.extend(format_signals(&change.cleared, "-"));
+        let time = match &change.time {
+            Some(time) => time.clone(),
+            None => change.origin.to_string(),
+        };
+        writeln!(w, "{time}\t{}\t{}", change.value, bits.join(" "))?;
+    }
+    writeln!(w)?;
+    writeln!(w, "BIT\tNAME\tSET\tCLEARED\tTOGGLES")?;
+    for toggles in &timeline.toggles {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            toggles.index,
+            toggles.name.as_deref().unwrap_or(""),
+            toggles.set,
+            toggles.cleared,
+            toggles.set + toggles.cleared
+        )?;
+    }
+    Ok(())
+}
+
+fn track(cli: &Cli, mapping: &Mapping, out: &mut impl Write) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut samples = Vec::new();
+    for (origin, input) in cli.batches()? {
+        match cli.sample(origin, &input) {
+            Ok(sample) => samples.push(sample),
+            Err(err) => {
+                eprintln!("{origin}: {input}: {err:#}");
+                ok = false;
+            }
+        }
+    }
+
+    let timeline = cli.timeline(samples, mapping)?;
+    match cli.format {
+        Format::Text => write_timeline(&timeline, out)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &timeline)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(ok)
+}
+
+// 返回是否所有值都处理成功, 失败的值输出到 stderr
+fn run(cli: &Cli, out: &mut impl Write) -> anyhow::Result<bool> {
+    let mapping = match &cli.map {
+        Some(path) => Mapping::load(path)?,
+        None => Mapping::default(),
+    };
+    if cli.changes {
+        return track(cli, &mapping, out);
+    }
+
+    let mut ok = true;
+    let mut decoded = Vec::new();
+    for (_, batch) in cli.batches()? {
+        let result = if cli.encode {
+            let tokens: Vec<&str> = batch
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .collect();
+            cli.encode(&tokens, &mapping)
+        } else {
+            cli.decode(&batch, &mapping)
+        };
+        match result {
+            Ok(value) => decoded.push(value),
+            Err(err) => {
+                eprintln!("{batch}: {err:#}");
+                ok = false;
+            }
+        }
+    }
+
+    match cli.format {
+        Format::Text => {
+            for value in &decoded {
+                write_text(value, out)?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &decoded)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(ok)
 }
 
 fn main() {
-    let inclusion = Inclusion::parse();
-    println!("{:?}", inclusion.binary());
+    let cli = Cli::parse();
+    let stdout = io::stdout().lock();
+    let result = if cli.changes && cli.format == Format::Text {
+        let mut tw = TabWriter::new(stdout).padding(2);
+        run(&cli, &mut tw).and_then(|ok| {
+            tw.flush()?;
+            Ok(ok)
+        })
+    } else {
+        let mut out = io::BufWriter::new(stdout);
+        run(&cli, &mut out).and_then(|ok| {
+            out.flush()?;
+            Ok(ok)
+        })
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{err:#}");
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+fn cli(args: &[&str]) -> Cli {
+    Cli::parse_from(["inclusion"].iter().chain(args))
+}
+
+// 按命令行参数解码唯一的一个值, 与 main 走同一条路径
+#[cfg(test)]
+fn decode(args: &[&str]) -> Decoded {
+    let cli = cli(args);
+    let batches = cli.batches().unwrap();
+    assert_eq!(batches.len(), 1);
+    cli.decode(&batches[0].1, &Mapping::default()).unwrap()
+}
+
+// 最初的 inclusion 输出的 (位号, '1')
+#[cfg(test)]
+fn idx_of_one(decoded: &Decoded) -> Vec<(usize, char)> {
+    decoded.bits.iter().map(|bit| (bit.index, '1')).collect()
+}
+
+#[test]
+fn test_mask() {
+    let mask = Mask::from_hex("0x1_0005").unwrap();
+    assert_eq!(mask.width(), 20);
+    assert_eq!(mask.to_hex(), "0x10005");
+    assert_eq!(mask.binary(), "10000000000000101");
+    assert_eq!(mask.set_bits(BitOrder::Lsb, 0), [0, 2, 16]);
+    assert_eq!(mask.set_bits(BitOrder::Lsb, 1), [1, 3, 17]);
+    assert_eq!(mask.set_bits(BitOrder::Msb, 0), [3, 17, 19]);
+    assert_eq!(mask.set_bits(BitOrder::Msb, 1), [4, 18, 20]);
+
+    // 超过 usize 的宽掩码
+    let wide = Mask::from_hex(&format!("8{}1", "0".repeat(40))).unwrap();
+    assert_eq!(wide.width(), 168);
+    assert_eq!(wide.set_bits(BitOrder::Lsb, 0), [0, 167]);
+    assert_eq!(wide.clone().with_width(256).unwrap().to_hex().len(), 2 + 64);
+    assert!(wide.with_width(100).is_err());
+
+    assert_eq!(Mask::from_hex("00").unwrap().binary(), "0");
+    assert!(Mask::from_hex("0x").is_err());
+    assert!(Mask::from_hex("0xFG").is_err());
+}
+
+#[test]
+fn test_mapping() {
+    let mapping = Mapping::parse(
+        "# 故障字
+0 过压告警
+2 电池 过温
+16 急停",
+    )
+    .unwrap();
+    assert!(Mapping::parse("0 a\n0 b").is_err());
+    assert!(Mapping::parse("0 a\n1 a").is_err());
+    assert!(Mapping::parse("x a").is_err());
+
+    let lsb = || cli(&["--order", "lsb", "--base", "0"]);
+    let decoded = lsb().decode("0x10005", &mapping).unwrap();
+    assert_eq!(
+        decoded.bits,
+        [
+            Signal {
+                index: 0,
+                name: Some("过压告警".into())
+            },
+            Signal {
+                index: 2,
+                name: Some("电池 过温".into())
+            },
+            Signal {
+                index: 16,
+                name: Some("急停".into())
+            },
+        ]
+    );
+    let mut text = Vec::new();
+    write_text(&decoded, &mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "0x10005\t0:过压告警 2:电池 过温 16:急停\n"
+    );
+
+    // 反向: 信号名和位号混用
+    let encoded = lsb().encode(&["急停", "0", "电池 过温"], &mapping).unwrap();
+    assert_eq!(encoded.value, "0x10005");
+    assert_eq!(
+        cli(&["--order", "msb", "--base", "1", "-w", "8"])
+            .encode(&["1", "8"], &Mapping::default())
+            .unwrap()
+            .value,
+        "0x81"
+    );
+    assert!(
+        cli(&["--order", "lsb", "--base", "0", "-w", "8"])
+            .encode(&["8"], &mapping)
+            .is_err()
+    );
+    assert!(cli(&[]).encode(&["0"], &mapping).is_err());
+    // 超大的位号和宽度是错误而不是溢出或耗尽内存
+    for index in ["18446744073709551615", "4000000000", "65536"] {
+        assert!(lsb().encode(&[index], &mapping).is_err());
+    }
+    assert_eq!(lsb().encode(&["65535"], &mapping).unwrap().width, MAX_BITS);
+    for width in ["99999999999999", "65537", "0"] {
+        assert!(Cli::try_parse_from(["inclusion", "-w", width, "0x1"]).is_err());
+    }
+    assert!(Mask::from_hex(&"F".repeat(MAX_BITS / 4 + 1)).is_err());
+    assert!(cli(&[]).encode(&["未知"], &mapping).is_err());
+}
+
+#[test]
+fn test_changes() {
+    let mapping = Mapping::parse("0 过压告警\n2 过温").unwrap();
+    let cli = cli(&["-c", "--order", "lsb", "--base", "0"]);
+    let samples = [
+        "2024-05-05 00:00:00.000 0x0",
+        "2024-05-05 00:00:01.000 0x5",
+        // 没有变化的值不输出
+        "2024-05-05 00:00:02.000 0x05",
+        "2024-05-05 00:00:03.000 0x104",
+        "0x1",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(line, input)| cli.sample(Origin::Line(line + 1), input).unwrap())
+    .collect();
+    let timeline = cli.timeline(samples, &mapping).unwrap();
+
+    let mut text = Vec::new();
+    write_timeline(&timeline, &mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "2024-05-05 00:00:00.000\t0x000\t
+2024-05-05 00:00:01.000\t0x005\t+0:过压告警 +2:过温
+2024-05-05 00:00:03.000\t0x104\t+8 -0:过压告警
+line 5\t0x001\t+0:过压告警 -2:过温 -8
+
+BIT\tNAME\tSET\tCLEARED\tTOGGLES
+0\t过压告警\t2\t1\t3
+2\t过温\t1\t1\t2
+8\t\t1\t1\t2
+"
+    );
+
+    // 从 IoT 日志荷载的嵌套字段中取值, 整数和字符串都可以
+    let cli = self::cli(&["-c", "--field", "fault", "--order", "lsb", "--base", "0"]);
+    let sample = cli
+        .sample(
+            Origin::Line(1),
+            r#"2024-05-05 00:00:03.294  [byts/EVENT/CN18b08cf88f1]  D:{"data":[{"fault":260}]}"#,
+        )
+        .unwrap();
+    assert_eq!(sample.time.as_deref(), Some("2024-05-05 00:00:03.294"));
+    assert_eq!(sample.mask.to_hex(), "0x104");
+    let sample = cli
+        .sample(
+            Origin::Line(1),
+            r#"2024-05-05 00:00:03.294  [zjkg]  D:{"fault":"0x0104"}"#,
+        )
+        .unwrap();
+    assert_eq!(sample.mask.to_hex(), "0x0104");
+    assert!(
+        cli.sample(
+            Origin::Line(1),
+            r#"2024-05-05 00:00:03.294  [zjkg]  D:{"alarm":1}"#
+        )
+        .is_err()
+    );
+
+    // 行号是文件中的实际行号, 空行和注释也计数; 命令行参数单独标记;
+    // 比 --width 宽的值和无效的值一样逐行报告后跳过
+    let path = std::env::temp_dir().join(format!("inclusion-{}.txt", std::process::id()));
+    fs::write(&path, "# 故障字\n\n0x0\n\n0x1\n0x1FF\n0x3\n").unwrap();
+    let mut out = Vec::new();
+    let tracked = run(
+        &self::cli(&[
+            "-c",
+            "--order",
+            "lsb",
+            "--base",
+            "0",
+            "-w",
+            "4",
+            "-i",
+            path.to_str().unwrap(),
+            "0x2",
+        ]),
+        &mut out,
+    );
+    fs::remove_file(&path).unwrap();
+    assert!(!tracked.unwrap());
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.starts_with("arg 1\t0x2\t+1\nline 3\t0x0\t-1\nline 5\t0x1\t+0\nline 7\t0x3\t+1\n"),
+        "{out}"
+    );
 }
 
 #[test]
 fn test_inclusion() {
-    let inclusion = Inclusion {
-        hex: "0xFF8".into(),
-    };
-    assert_eq!(inclusion.binary(), "111111111000");
-    assert_eq!(inclusion.count_ones(), 9);
+    // --hex 与位置参数等价
+    assert_eq!(decode(&["--hex", "0xFF8"]), decode(&["0xFF8"]));
+    let inclusion = decode(&["--hex", "0xFF8"]);
+    assert_eq!(inclusion.binary, "111111111000");
+    assert_eq!(inclusion.ones, 9);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -100,13 +931,11 @@ fn test_inclusion() {
         ]
     );
 
-    let inclusion = Inclusion {
-        hex: "0xFFFFFFFFE".into(),
-    };
-    assert_eq!(inclusion.binary(), "111111111111111111111111111111111110");
-    assert_eq!(inclusion.count_ones(), 35);
+    let inclusion = decode(&["--hex", "0xFFFFFFFFE"]);
+    assert_eq!(inclusion.binary, "111111111111111111111111111111111110");
+    assert_eq!(inclusion.ones, 35);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -146,16 +975,14 @@ fn test_inclusion() {
         ]
     );
 
-    let inclusion = Inclusion {
-        hex: "0xFFFFFFFFFFFE".into(),
-    };
+    let inclusion = decode(&["--hex", "0xFFFFFFFFFFFE"]);
     assert_eq!(
-        inclusion.binary(),
+        inclusion.binary,
         "111111111111111111111111111111111111111111111110"
     );
-    assert_eq!(inclusion.count_ones(), 47);
+    assert_eq!(inclusion.ones, 47);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -207,13 +1034,11 @@ fn test_inclusion() {
         ]
     );
 
-    let inclusion = Inclusion {
-        hex: "0xffff8".into(),
-    };
-    assert_eq!(inclusion.binary(), "11111111111111111000");
-    assert_eq!(inclusion.count_ones(), 17);
+    let inclusion = decode(&["--hex", "0xffff8"]);
+    assert_eq!(inclusion.binary, "11111111111111111000");
+    assert_eq!(inclusion.ones, 17);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -235,16 +1060,14 @@ fn test_inclusion() {
         ]
     );
 
-    let inclusion = Inclusion {
-        hex: "0x1FD3C00800000000".into(),
-    };
+    let inclusion = decode(&["--hex", "0x1FD3C00800000000", "-w", "61"]);
     assert_eq!(
-        inclusion.binary(),
+        inclusion.binary,
         "1111111010011110000000000100000000000000000000000000000000000"
     );
-    assert_eq!(inclusion.count_ones(), 13);
+    assert_eq!(inclusion.ones, 13);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -262,16 +1085,30 @@ fn test_inclusion() {
         ]
     );
 
-    let inclusion = Inclusion { hex: "0x0000000050000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000".into() };
-    assert_eq!(inclusion.binary(), "1010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
-    assert_eq!(inclusion.count_ones(), 2);
-    assert_eq!(inclusion.idx_of_one(), vec![(1, '1'), (3, '1')]);
+    let inclusion = decode(&[
+        "--hex",
+        "0x0000000050000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "-w",
+        "1087",
+    ]);
+    assert_eq!(
+        inclusion.binary,
+        "1010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000"
+    );
+    assert_eq!(inclusion.ones, 2);
+    assert_eq!(idx_of_one(&inclusion), vec![(1, '1'), (3, '1')]);
 
-    let inclusion = Inclusion { hex: "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE".into() };
-    assert_eq!(inclusion.binary(), "1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111110");
-    assert_eq!(inclusion.count_ones(), 1119);
+    let inclusion = decode(&[
+        "--hex",
+        "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE",
+    ]);
+    assert_eq!(
+        inclusion.binary,
+        "1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111110"
+    );
+    assert_eq!(inclusion.ones, 1119);
     assert_eq!(
-        inclusion.idx_of_one(),
+        idx_of_one(&inclusion),
         vec![
             (1, '1'),
             (2, '1'),
@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::{Subcommand, ValueEnum};
+use tabwriter::TabWriter;
+
+use crate::{Coordinate, Distance, Itinerary};
+
+// trip-cli (nom) 和 trip (winnow) 共用的子命令, 两者只有解析器不同
+
+pub const EXAMPLE: &str = r#"Russia
+    Vladivostok : 43.131621,131.923828 : 4
+    Ulan Ude : 51.841624,107.608101 : 2
+    Saint Petersburg : 59.939977,30.315785 : 10
+Norway
+    Oslo : 59.914289,10.738739 : 2
+    Bergen : 60.388533,5.331856 : 4
+Ukraine
+    Kiev : 50.456001,30.50384 : 3
+Switzerland
+    Wengen : 46.608265,7.922065 : 3
+    Bern : 46.949076,7.448151 : 1"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    Haversine,
+    Vincenty,
+}
+
+impl From<Method> for Distance {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Haversine => Distance::Haversine,
+            Method::Vincenty => Distance::Vincenty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Geojson,
+    Kml,
+    Gpx,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the parsed itinerary as JSON
+    Show {
+        /// Itinerary file, the built-in example when omitted
+        file: Option<PathBuf>,
+    },
+    /// Print path length, bounding box and centroid of each country
+    Geo {
+        /// Itinerary file, the built-in example when omitted
+        file: Option<PathBuf>,
+
+        /// Distance formula
+        #[arg(short, long, value_enum, default_value_t = Method::Haversine)]
+        method: Method,
+
+        /// Also print the destination nearest to this point in each country
+        #[arg(long, value_name = "LAT,LON")]
+        near: Option<Coordinate>,
+    },
+    /// Export destinations and per-country routes for map viewers
+    Export {
+        /// Itinerary file, the built-in example when omitted
+        file: Option<PathBuf>,
+
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Geojson)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+// 不给文件时使用内置示例
+pub fn read(file: Option<&PathBuf>) -> Result<String, String> {
+    match file {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display())),
+        None => Ok(EXAMPLE.to_string()),
+    }
+}
+
+// 输出到 `| head` 之类提前关闭的管道时静默结束
+pub fn stdout_result(result: io::Result<()>) -> Result<(), String> {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn export(itinerary: &Itinerary, format: ExportFormat, w: &mut impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Geojson => itinerary.write_geojson(w),
+        ExportFormat::Kml => itinerary.write_kml(w),
+        ExportFormat::Gpx => itinerary.write_gpx(w),
+    }
+}
+
+// load 用各自的解析器读取行程文件
+pub fn run(
+    command: Command,
+    load: impl Fn(Option<&PathBuf>) -> Result<Itinerary, String>,
+) -> Result<(), String> {
+    match command {
+        Command::Show { file } => {
+            let itinerary = load(file.as_ref())?;
+            let json = serde_json::to_string_pretty(&itinerary).map_err(|e| e.to_string())?;
+            stdout_result(writeln!(io::stdout().lock(), "{json}"))?;
+        }
+        Command::Geo { file, method, near } => {
+            let itinerary = load(file.as_ref())?;
+            let mut tw = TabWriter::new(io::stdout().lock()).padding(2);
+            stdout_result(
+                itinerary
+                    .write_geo_table(method.into(), near.as_ref(), &mut tw)
+                    .and_then(|()| tw.flush()),
+            )?;
+        }
+        Command::Export {
+            file,
+            format,
+            output,
+        } => {
+            let itinerary = load(file.as_ref())?;
+            match &output {
+                Some(path) => fs::File::create(path)
+                    .and_then(|file| {
+                        let mut w = io::BufWriter::new(file);
+                        export(&itinerary, format, &mut w).and_then(|()| w.flush())
+                    })
+                    .map_err(|e| format!("{}: {e}", path.display()))?,
+                None => {
+                    let mut w = io::BufWriter::new(io::stdout().lock());
+                    stdout_result(export(&itinerary, format, &mut w).and_then(|()| w.flush()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
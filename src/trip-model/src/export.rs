@@ -0,0 +1,229 @@
+use std::io::{self, Write};
+
+use serde_json::{Value, json};
+
+use crate::{Coordinate, Country, Itinerary};
+
+// GeoJSON 的坐标顺序是经度在前
+fn position(c: &Coordinate) -> Value {
+    json!([c.lon, c.lat])
+}
+
+// XML 文本和属性中的特殊字符
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Country {
+    // LineString 至少需要两个点, 只有一个目的地时为 None
+    fn route(&self) -> Option<Vec<&Coordinate>> {
+        (self.destinations.len() >= 2).then(|| {
+            self.destinations
+                .iter()
+                .map(|destination| &destination.coordinate)
+                .collect()
+        })
+    }
+}
+
+impl Itinerary {
+    // 每个目的地一个 Point, 每个国家按行程顺序一条 LineString
+    pub fn to_geojson(&self) -> Value {
+        let mut features = Vec::new();
+        for (country, destination) in self.destinations() {
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": position(&destination.coordinate),
+                },
+                "properties": {
+                    "name": destination.name,
+                    "tickets": destination.tickets,
+                    "country": country.name,
+                },
+            }));
+        }
+        for country in &self.countries {
+            let Some(route) = country.route() else {
+                continue;
+            };
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": route.into_iter().map(position).collect::<Vec<_>>(),
+                },
+                "properties": {
+                    "country": country.name,
+                },
+            }));
+        }
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+
+    pub fn write_geojson(&self, w: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *w, &self.to_geojson())?;
+        writeln!(w)
+    }
+
+    // 每个国家一个 Folder, 包含目的地的 Placemark 和路线
+    pub fn write_kml(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(w, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+        writeln!(w, "<Document>")?;
+        for country in &self.countries {
+            let name = escape(&country.name);
+            writeln!(w, "  <Folder>")?;
+            writeln!(w, "    <name>{name}</name>")?;
+            for destination in &country.destinations {
+                let c = &destination.coordinate;
+                writeln!(w, "    <Placemark>")?;
+                writeln!(w, "      <name>{}</name>", escape(&destination.name))?;
+                writeln!(w, "      <ExtendedData>")?;
+                writeln!(
+                    w,
+                    r#"        <Data name="tickets"><value>{}</value></Data>"#,
+                    destination.tickets
+                )?;
+                writeln!(
+                    w,
+                    r#"        <Data name="country"><value>{name}</value></Data>"#
+                )?;
+                writeln!(w, "      </ExtendedData>")?;
+                writeln!(
+                    w,
+                    "      <Point><coordinates>{},{}</coordinates></Point>",
+                    c.lon, c.lat
+                )?;
+                writeln!(w, "    </Placemark>")?;
+            }
+            if let Some(route) = country.route() {
+                let coordinates: Vec<String> = route
+                    .iter()
+                    .map(|c| format!("{},{}", c.lon, c.lat))
+                    .collect();
+                writeln!(w, "    <Placemark>")?;
+                writeln!(w, "      <name>{name}</name>")?;
+                writeln!(
+                    w,
+                    "      <LineString><coordinates>{}</coordinates></LineString>",
+                    coordinates.join(" ")
+                )?;
+                writeln!(w, "    </Placemark>")?;
+            }
+            writeln!(w, "  </Folder>")?;
+        }
+        writeln!(w, "</Document>")?;
+        writeln!(w, "</kml>")
+    }
+
+    // 每个国家一条 GPX 1.1 路线 (rte), 目的地为路线点
+    pub fn write_gpx(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<gpx version="1.1" creator="trip" xmlns="http://www.topografix.com/GPX/1/1">"#
+        )?;
+        for country in &self.countries {
+            writeln!(w, "  <rte>")?;
+            writeln!(w, "    <name>{}</name>", escape(&country.name))?;
+            for destination in &country.destinations {
+                let c = &destination.coordinate;
+                writeln!(w, r#"    <rtept lat="{}" lon="{}">"#, c.lat, c.lon)?;
+                writeln!(w, "      <name>{}</name>", escape(&destination.name))?;
+                writeln!(w, "      <desc>tickets: {}</desc>", destination.tickets)?;
+                writeln!(w, "    </rtept>")?;
+            }
+            writeln!(w, "  </rte>")?;
+        }
+        writeln!(w, "</gpx>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Destination;
+
+    fn itinerary() -> Itinerary {
+        let destination = |name: &str, lat, lon, tickets| Destination {
+            name: name.to_string(),
+            coordinate: Coordinate { lat, lon },
+            tickets,
+        };
+        Itinerary {
+            countries: vec![
+                Country {
+                    name: "Norway".to_string(),
+                    destinations: vec![
+                        destination("Oslo", 59.914289, 10.738739, 2),
+                        destination("Bergen", 60.388533, 5.331856, 4),
+                    ],
+                },
+                Country {
+                    name: "Ukraine".to_string(),
+                    destinations: vec![destination("Kiev & <Co>", 50.456001, 30.50384, 3)],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_geojson() {
+        let geojson = itinerary().to_geojson();
+        let features = geojson["features"].as_array().unwrap();
+        // 三个点, 只有一个目的地的国家没有 LineString
+        assert_eq!(features.len(), 4);
+        assert_eq!(
+            features[0],
+            json!({
+                "type": "Feature",
+                "geometry": {"type": "Point", "coordinates": [10.738739, 59.914289]},
+                "properties": {"name": "Oslo", "tickets": 2, "country": "Norway"},
+            })
+        );
+        assert_eq!(features[3]["geometry"]["type"], "LineString");
+        assert_eq!(
+            features[3]["geometry"]["coordinates"],
+            json!([[10.738739, 59.914289], [5.331856, 60.388533]])
+        );
+        assert_eq!(features[3]["properties"]["country"], "Norway");
+    }
+
+    #[test]
+    fn test_kml_and_gpx() {
+        let mut kml = Vec::new();
+        itinerary().write_kml(&mut kml).unwrap();
+        let kml = String::from_utf8(kml).unwrap();
+        assert!(kml.contains("<name>Kiev &amp; &lt;Co&gt;</name>"));
+        assert!(kml.contains("<Point><coordinates>10.738739,59.914289</coordinates></Point>"));
+        assert!(kml.contains(
+            "<LineString><coordinates>10.738739,59.914289 5.331856,60.388533</coordinates></LineString>"
+        ));
+        assert_eq!(kml.matches("<LineString>").count(), 1);
+        assert!(kml.ends_with("</Document>\n</kml>\n"));
+
+        let mut gpx = Vec::new();
+        itinerary().write_gpx(&mut gpx).unwrap();
+        let gpx = String::from_utf8(gpx).unwrap();
+        assert_eq!(gpx.matches("<rte>").count(), 2);
+        assert!(gpx.contains(
+            "    <rtept lat=\"60.388533\" lon=\"5.331856\">\n      <name>Bergen</name>\n      <desc>tickets: 4</desc>\n"
+        ));
+    }
+}
@@ -0,0 +1,376 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Coordinate, Country, Destination, Itinerary};
+
+// 地球平均半径 (IUGG), 单位米
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+// WGS-84 椭球
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Distance {
+    // 球面大圆距离, 误差约 0.5%
+    #[default]
+    Haversine,
+    // WGS-84 椭球上的测地线距离, 精确到毫米级
+    Vincenty,
+}
+
+impl Distance {
+    // 单位米; Vincenty 在近乎对跖的两点间不收敛, 此时退回 haversine
+    pub fn between(&self, a: &Coordinate, b: &Coordinate) -> f64 {
+        match self {
+            Distance::Haversine => a.haversine(b),
+            Distance::Vincenty => a.vincenty(b).unwrap_or_else(|| a.haversine(b)),
+        }
+    }
+}
+
+impl Coordinate {
+    pub fn haversine(&self, other: &Coordinate) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+    }
+
+    // Vincenty 反解, 不收敛时返回 None
+    pub fn vincenty(&self, other: &Coordinate) -> Option<f64> {
+        let l = (other.lon - self.lon).to_radians();
+        let u1 = ((1.0 - WGS84_F) * self.lat.to_radians().tan()).atan();
+        let u2 = ((1.0 - WGS84_F) * other.lat.to_radians().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let (sin_u2, cos_u2) = u2.sin_cos();
+
+        let mut lambda = l;
+        for _ in 0..200 {
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+                + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+            .sqrt();
+            // 重合的两点
+            if sin_sigma == 0.0 {
+                return Some(0.0);
+            }
+            let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+            let sigma = sin_sigma.atan2(cos_sigma);
+            let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+            let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+            // 两点都在赤道上时 cos2_alpha 为 0
+            let cos_2sigma_m = if cos2_alpha == 0.0 {
+                0.0
+            } else {
+                cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+            };
+            let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+            let previous = lambda;
+            lambda = l
+                + (1.0 - c)
+                    * WGS84_F
+                    * sin_alpha
+                    * (sigma
+                        + c * sin_sigma
+                            * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+            if (lambda - previous).abs() > 1e-12 {
+                continue;
+            }
+
+            let u_sq = cos2_alpha * (WGS84_A.powi(2) - WGS84_B.powi(2)) / WGS84_B.powi(2);
+            let a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+            return Some(WGS84_B * a * (sigma - delta_sigma));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min: Coordinate,
+    pub max: Coordinate,
+}
+
+impl BoundingBox {
+    // 不处理跨越 180° 经线的情况
+    pub fn around<'a>(coordinates: impl IntoIterator<Item = &'a Coordinate>) -> Option<Self> {
+        coordinates.into_iter().fold(None, |bbox, c| {
+            Some(match bbox {
+                None => BoundingBox { min: *c, max: *c },
+                Some(BoundingBox { min, max }) => BoundingBox {
+                    min: Coordinate {
+                        lat: min.lat.min(c.lat),
+                        lon: min.lon.min(c.lon),
+                    },
+                    max: Coordinate {
+                        lat: max.lat.max(c.lat),
+                        lon: max.lon.max(c.lon),
+                    },
+                },
+            })
+        })
+    }
+}
+
+// 球面上的几何中心: 转为单位向量求平均, 跨 180° 经线时也正确
+pub fn centroid<'a>(coordinates: impl IntoIterator<Item = &'a Coordinate>) -> Option<Coordinate> {
+    let (mut x, mut y, mut z, mut n) = (0.0, 0.0, 0.0, 0);
+    for c in coordinates {
+        let (lat, lon) = (c.lat.to_radians(), c.lon.to_radians());
+        x += lat.cos() * lon.cos();
+        y += lat.cos() * lon.sin();
+        z += lat.sin();
+        n += 1;
+    }
+    if n == 0 {
+        return None;
+    }
+    let n = f64::from(n);
+    let (x, y, z) = (x / n, y / n, z / n);
+    Some(Coordinate {
+        lat: z.atan2(x.hypot(y)).to_degrees(),
+        lon: y.atan2(x).to_degrees(),
+    })
+}
+
+impl Country {
+    fn coordinates(&self) -> impl Iterator<Item = &Coordinate> {
+        self.destinations
+            .iter()
+            .map(|destination| &destination.coordinate)
+    }
+
+    // 按顺序依次经过各目的地的总距离, 单位米
+    pub fn path_length(&self, distance: Distance) -> f64 {
+        self.destinations
+            .windows(2)
+            .map(|pair| distance.between(&pair[0].coordinate, &pair[1].coordinate))
+            // 空迭代器的 f64 sum 是 -0.0
+            .fold(0.0, |length, leg| length + leg)
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::around(self.coordinates())
+    }
+
+    pub fn centroid(&self) -> Option<Coordinate> {
+        centroid(self.coordinates())
+    }
+
+    // 离 point 最近的目的地及距离 (米)
+    pub fn nearest(&self, point: &Coordinate, distance: Distance) -> Option<(&Destination, f64)> {
+        nearest(self.destinations.iter(), point, distance)
+    }
+}
+
+impl Itinerary {
+    pub fn nearest(
+        &self,
+        point: &Coordinate,
+        distance: Distance,
+    ) -> Option<(&Country, &Destination, f64)> {
+        self.countries
+            .iter()
+            .filter_map(|country| {
+                country
+                    .nearest(point, distance)
+                    .map(|(destination, meters)| (country, destination, meters))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+    }
+
+    // 每个国家一行, 用 `\t` 分隔, 由调用方对齐 (例如 TabWriter); 距离单位为千米
+    pub fn write_geo_table(
+        &self,
+        distance: Distance,
+        near: Option<&Coordinate>,
+        w: &mut impl Write,
+    ) -> io::Result<()> {
+        write!(
+            w,
+            "COUNTRY\tDESTINATIONS\tPATH KM\tSOUTH WEST\tNORTH EAST\tCENTROID"
+        )?;
+        if near.is_some() {
+            write!(w, "\tNEAREST\tKM")?;
+        }
+        writeln!(w)?;
+
+        for country in &self.countries {
+            let (south_west, north_east) = match country.bounding_box() {
+                Some(bbox) => (format_coordinate(&bbox.min), format_coordinate(&bbox.max)),
+                None => Default::default(),
+            };
+            write!(
+                w,
+                "{}\t{}\t{:.1}\t{south_west}\t{north_east}\t{}",
+                country.name,
+                country.destinations.len(),
+                country.path_length(distance) / 1000.0,
+                country
+                    .centroid()
+                    .map(|c| format_coordinate(&c))
+                    .unwrap_or_default()
+            )?;
+            if let Some(point) = near {
+                match country.nearest(point, distance) {
+                    Some((destination, meters)) => {
+                        write!(w, "\t{}\t{:.1}", destination.name, meters / 1000.0)?
+                    }
+                    None => write!(w, "\t\t")?,
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+fn nearest<'a>(
+    destinations: impl Iterator<Item = &'a Destination>,
+    point: &Coordinate,
+    distance: Distance,
+) -> Option<(&'a Destination, f64)> {
+    destinations
+        .map(|destination| {
+            (
+                destination,
+                distance.between(point, &destination.coordinate),
+            )
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+fn format_coordinate(c: &Coordinate) -> String {
+    format!("{:.6},{:.6}", c.lat, c.lon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinate(lat: f64, lon: f64) -> Coordinate {
+        Coordinate { lat, lon }
+    }
+
+    fn destination(name: &str, lat: f64, lon: f64) -> Destination {
+        Destination {
+            name: name.to_string(),
+            coordinate: coordinate(lat, lon),
+            tickets: 1,
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        // 赤道上 1° 经度
+        let a = coordinate(0.0, 0.0);
+        let b = coordinate(0.0, 1.0);
+        assert!((a.haversine(&b) - 111_195.08).abs() < 0.01);
+        assert!((a.vincenty(&b).unwrap() - 111_319.491).abs() < 0.001);
+
+        // Vincenty 原文的例子: Flinders Peak -> Buninyong
+        let flinders = coordinate(-37.951_033_42, 144.424_867_89);
+        let buninyong = coordinate(-37.652_821_14, 143.926_495_54);
+        assert!((flinders.vincenty(&buninyong).unwrap() - 54_972.271).abs() < 0.001);
+        assert_eq!(flinders.vincenty(&flinders), Some(0.0));
+
+        // 对跖点不收敛, 退回 haversine
+        let antipode = coordinate(0.5, 179.7);
+        assert_eq!(a.vincenty(&antipode), None);
+        assert_eq!(
+            Distance::Vincenty.between(&a, &antipode),
+            a.haversine(&antipode)
+        );
+    }
+
+    #[test]
+    fn test_country() {
+        let country = Country {
+            name: "Norway".to_string(),
+            destinations: vec![
+                destination("Oslo", 59.914289, 10.738739),
+                destination("Bergen", 60.388533, 5.331856),
+                destination("Tromso", 69.649205, 18.955324),
+            ],
+        };
+        let length = country.path_length(Distance::Haversine);
+        let legs = country.destinations[0]
+            .coordinate
+            .haversine(&country.destinations[1].coordinate)
+            + country.destinations[1]
+                .coordinate
+                .haversine(&country.destinations[2].coordinate);
+        assert_eq!(length, legs);
+        assert!((length / 1000.0 - 1509.9).abs() < 0.1, "{length}");
+
+        assert_eq!(
+            country.bounding_box(),
+            Some(BoundingBox {
+                min: coordinate(59.914289, 5.331856),
+                max: coordinate(69.649205, 18.955324),
+            })
+        );
+
+        let (nearest, meters) = country
+            .nearest(&coordinate(60.0, 6.0), Distance::Vincenty)
+            .unwrap();
+        assert_eq!(nearest.name, "Bergen");
+        assert!(meters < 60_000.0);
+
+        let single = Country {
+            name: "Ukraine".to_string(),
+            destinations: vec![destination("Kiev", 50.456001, 30.50384)],
+        };
+        assert_eq!(
+            format!("{:.1}", single.path_length(Distance::Vincenty)),
+            "0.0"
+        );
+
+        // 跨越 180° 经线时中心点不应落在 0° 经线附近
+        let center = centroid(&[coordinate(0.0, 179.0), coordinate(0.0, -179.0)]).unwrap();
+        assert!(center.lat.abs() < 1e-9 && (center.lon.abs() - 180.0).abs() < 1e-9);
+        assert_eq!(centroid(&[]), None);
+    }
+
+    #[test]
+    fn test_geo_table() {
+        let itinerary = Itinerary {
+            countries: vec![Country {
+                name: "Switzerland".to_string(),
+                destinations: vec![
+                    destination("Wengen", 46.608265, 7.922065),
+                    destination("Bern", 46.949076, 7.448151),
+                ],
+            }],
+        };
+        let mut out = Vec::new();
+        itinerary
+            .write_geo_table(
+                Distance::Haversine,
+                Some(&coordinate(46.95, 7.45)),
+                &mut out,
+            )
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let row: Vec<&str> = out.lines().nth(1).unwrap().split('\t').collect();
+        assert_eq!(row[..2], ["Switzerland", "2"]);
+        assert_eq!(row[3..5], ["46.608265,7.448151", "46.949076,7.922065"]);
+        assert_eq!(row[6..], ["Bern", "0.2"]);
+    }
+}
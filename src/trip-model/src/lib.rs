@@ -0,0 +1,110 @@
+#[cfg(feature = "cli")]
+pub mod cli;
+mod export;
+mod geo;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+pub use geo::*;
+
+// trip-lang (nom) 和 trip-parser (winnow) 共用的行程模型
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinate {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+// `纬度,经度`, 与行程文本中的写法相同
+impl FromStr for Coordinate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (lat, lon) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected `lat,lon`, got {s:?}"))?;
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("invalid coordinate {value:?}: {e}"))
+        };
+        Coordinate::new(parse(lat)?, parse(lon)?)
+    }
+}
+
+impl Coordinate {
+    // 两个解析器和 `FromStr` 共用的检查; 超出范围的值, 包括 inf 和 NaN, 都是错误
+    pub fn new(lat: f64, lon: f64) -> Result<Self, String> {
+        if !lat.is_finite()
+            || !lon.is_finite()
+            || !(-90.0..=90.0).contains(&lat)
+            || !(-180.0..=180.0).contains(&lon)
+        {
+            return Err(format!("coordinate {lat},{lon} out of range"));
+        }
+        Ok(Coordinate { lat, lon })
+    }
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.lat, self.lon)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Destination {
+    pub name: String,
+    pub coordinate: Coordinate,
+    pub tickets: u32,
+}
+
+// 一个国家及其目的地, 保持输入中的顺序
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Country {
+    pub name: String,
+    pub destinations: Vec<Destination>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Itinerary {
+    pub countries: Vec<Country>,
+}
+
+impl Itinerary {
+    pub fn country(&self, name: &str) -> Option<&Country> {
+        self.countries.iter().find(|country| country.name == name)
+    }
+
+    // 所有目的地及其所在国家, 按输入顺序
+    pub fn destinations(&self) -> impl Iterator<Item = (&Country, &Destination)> {
+        self.countries.iter().flat_map(|country| {
+            country
+                .destinations
+                .iter()
+                .map(move |destination| (country, destination))
+        })
+    }
+}
+
+// 与 parse_trips 的输入格式一致, 目的地缩进 4 个空格, 例如
+// `Norway\n    Oslo : 59.914289,10.738739 : 2\n`
+impl fmt::Display for Itinerary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for country in &self.countries {
+            writeln!(f, "{}", country.name)?;
+            for destination in &country.destinations {
+                writeln!(
+                    f,
+                    "    {} : {} : {}",
+                    destination.name, destination.coordinate, destination.tickets
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
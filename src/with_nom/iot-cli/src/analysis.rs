@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta};
+use iot_log_parser::LogRecord;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::output::Format;
+use crate::payload::IotPayload;
+
+pub struct AnalysisConfig {
+    // epoch 类设备时间 (Byts.timestamp, logTime) 转换为本地时间所用的时区
+    pub utc_offset: FixedOffset,
+    // 周期上送间隔超过该值记为一次断档
+    pub max_gap: TimeDelta,
+}
+
+impl Default for AnalysisConfig {
+    // 网关日志使用北京时间, 周期上送一般为 5 分钟
+    fn default() -> Self {
+        AnalysisConfig {
+            utc_offset: FixedOffset::east_opt(8 * 3600).unwrap(),
+            max_gap: TimeDelta::minutes(5),
+        }
+    }
+}
+
+// 一个设备在某个时钟来源上的一次观测
+struct Sample<'r> {
+    device: &'r str,
+    // 设备时间来自哪个字段: ts / timestamp / logTime / t
+    source: &'static str,
+    // 字段存在但无法解析时为 None
+    device_time: Option<NaiveDateTime>,
+    // report/period 和 byts DATA 是周期上送, 用于断档检测
+    periodic: bool,
+}
+
+// 两次周期上送之间超过 max_gap 的断档
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gap {
+    pub device: String,
+    pub source: &'static str,
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    samples: usize,
+    invalid_times: usize,
+    min_skew: i64,
+    max_skew: i64,
+    total_skew: i64,
+    out_of_order: usize,
+    last_device_time: Option<NaiveDateTime>,
+    last_periodic: Option<NaiveDateTime>,
+    gaps: usize,
+    max_gap: TimeDelta,
+}
+
+// 每个设备、每个时钟来源一行, 偏差为设备时间减服务器时间, 单位毫秒
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceSummary {
+    pub device: String,
+    pub source: &'static str,
+    pub samples: usize,
+    pub invalid_times: usize,
+    pub min_skew_ms: i64,
+    pub max_skew_ms: i64,
+    pub mean_skew_ms: i64,
+    pub out_of_order: usize,
+    pub gaps: usize,
+    pub max_gap_secs: i64,
+}
+
+pub struct Analyzer {
+    config: AnalysisConfig,
+    stats: BTreeMap<(String, &'static str), Stats>,
+    gaps: Vec<Gap>,
+    // 最后一条记录的服务器时间, 用于检测结尾的断档
+    last_server_time: Option<NaiveDateTime>,
+}
+
+fn local_time(millis: i64, offset: FixedOffset) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp_millis(millis).map(|time| time.with_timezone(&offset).naive_local())
+}
+
+// logTime 可能是 epoch 毫秒 (数字或数字字符串), 也可能是日期时间字符串.
+// 不带时区的日期时间视为 offset 时区的本地时间
+fn log_time(value: &Value, offset: FixedOffset) -> Option<NaiveDateTime> {
+    match value {
+        Value::Number(n) => local_time(n.as_i64()?, offset),
+        Value::String(s) => match s.parse() {
+            Ok(millis) => local_time(millis, offset),
+            Err(_) => DateTime::parse_from_rfc3339(s)
+                .map(|time| time.with_timezone(&offset).naive_local())
+                .ok()
+                .or_else(|| {
+                    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+                        .iter()
+                        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+                }),
+        },
+        _ => None,
+    }
+}
+
+fn samples<'r>(record: &'r LogRecord<IotPayload>, offset: FixedOffset) -> Vec<Sample<'r>> {
+    let periodic = match record.topic.as_slice() {
+        [.., report, period] => report == "report" && period == "period",
+        _ => false,
+    };
+
+    match &record.payload {
+        IotPayload::Yjhy(yjhys) => yjhys
+            .iter()
+            .map(|yjhy| Sample {
+                device: &yjhy.ied_name,
+                source: "ts",
+                device_time: NaiveDateTime::parse_from_str(&yjhy.ts, "%Y-%m-%d %H:%M:%S").ok(),
+                periodic,
+            })
+            .collect(),
+        IotPayload::Byts(byts) => {
+            let periodic = record.topic.get(1).is_some_and(|level| level == "DATA");
+            let timestamp = Sample {
+                device: &byts.device_key,
+                source: "timestamp",
+                device_time: i64::try_from(byts.timestamp)
+                    .ok()
+                    .and_then(|millis| local_time(millis, offset)),
+                periodic,
+            };
+            let log_time = byts.data.get("logTime").map(|value| Sample {
+                device: &byts.device_key,
+                source: "logTime",
+                device_time: log_time(value, offset),
+                periodic: false,
+            });
+            std::iter::once(timestamp).chain(log_time).collect()
+        }
+        IotPayload::Zjkg(zjkg) => zjkg
+            .images
+            .iter()
+            .map(|image| Sample {
+                device: &zjkg.mid,
+                source: "t",
+                device_time: image.time(),
+                periodic: false,
+            })
+            .collect(),
+    }
+}
+
+impl Analyzer {
+    pub fn new(config: AnalysisConfig) -> Self {
+        Analyzer {
+            config,
+            stats: BTreeMap::new(),
+            gaps: Vec::new(),
+            last_server_time: None,
+        }
+    }
+
+    // 记录需按日志中的顺序传入
+    pub fn observe(&mut self, record: &LogRecord<IotPayload>) {
+        self.last_server_time = self.last_server_time.max(Some(record.server_time));
+        for sample in samples(record, self.config.utc_offset) {
+            let key = (sample.device.to_string(), sample.source);
+            let stats = self.stats.entry(key).or_default();
+
+            let Some(device_time) = sample.device_time else {
+                stats.invalid_times += 1;
+                continue;
+            };
+            let skew = (device_time - record.server_time).num_milliseconds();
+            if stats.samples == 0 {
+                (stats.min_skew, stats.max_skew) = (skew, skew);
+            }
+            stats.samples += 1;
+            stats.min_skew = stats.min_skew.min(skew);
+            stats.max_skew = stats.max_skew.max(skew);
+            stats.total_skew += skew;
+
+            if stats
+                .last_device_time
+                .is_some_and(|last| device_time < last)
+            {
+                stats.out_of_order += 1;
+            }
+            stats.last_device_time = Some(device_time);
+
+            if !sample.periodic {
+                continue;
+            }
+            if let Some(last) = stats.last_periodic {
+                let gap = record.server_time - last;
+                if gap > self.config.max_gap {
+                    stats.gaps += 1;
+                    stats.max_gap = stats.max_gap.max(gap);
+                    self.gaps.push(Gap {
+                        device: sample.device.to_string(),
+                        source: sample.source,
+                        from: last,
+                        to: record.server_time,
+                    });
+                }
+            }
+            stats.last_periodic = Some(record.server_time);
+        }
+    }
+
+    // 输入结束后调用: 最后一次周期上送到 until (默认为最后一条记录的时间) 超过 max_gap 也算断档
+    pub fn finish(&mut self, until: Option<NaiveDateTime>) {
+        let Some(end) = until.or(self.last_server_time) else {
+            return;
+        };
+        for ((device, source), stats) in &mut self.stats {
+            let Some(last) = stats.last_periodic else {
+                continue;
+            };
+            let gap = end - last;
+            if gap > self.config.max_gap {
+                stats.gaps += 1;
+                stats.max_gap = stats.max_gap.max(gap);
+                self.gaps.push(Gap {
+                    device: device.clone(),
+                    source,
+                    from: last,
+                    to: end,
+                });
+                stats.last_periodic = Some(end);
+            }
+        }
+    }
+
+    pub fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    pub fn summary(&self) -> Vec<DeviceSummary> {
+        self.stats
+            .iter()
+            .map(|((device, source), stats)| DeviceSummary {
+                device: device.clone(),
+                source,
+                samples: stats.samples,
+                invalid_times: stats.invalid_times,
+                min_skew_ms: stats.min_skew,
+                max_skew_ms: stats.max_skew,
+                mean_skew_ms: stats.total_skew / stats.samples.max(1) as i64,
+                out_of_order: stats.out_of_order,
+                gaps: stats.gaps,
+                max_gap_secs: stats.max_gap.num_seconds(),
+            })
+            .collect()
+    }
+}
+
+pub fn write_summary(
+    format: Format,
+    w: &mut impl Write,
+    summary: &[DeviceSummary],
+) -> io::Result<()> {
+    match format {
+        Format::Jsonl => {
+            for row in summary {
+                serde_json::to_writer(&mut *w, row)?;
+                writeln!(w)?;
+            }
+        }
+        Format::Csv => {
+            writeln!(
+                w,
+                "device,source,samples,invalid_times,min_skew_ms,max_skew_ms,mean_skew_ms,out_of_order,gaps,max_gap_secs"
+            )?;
+            for row in summary {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    row.device,
+                    row.source,
+                    row.samples,
+                    row.invalid_times,
+                    row.min_skew_ms,
+                    row.max_skew_ms,
+                    row.mean_skew_ms,
+                    row.out_of_order,
+                    row.gaps,
+                    row.max_gap_secs
+                )?;
+            }
+        }
+        Format::Table => {
+            writeln!(
+                w,
+                "DEVICE\tSOURCE\tSAMPLES\tINVALID TIMES\tMIN SKEW\tMAX SKEW\tMEAN SKEW\tOUT OF ORDER\tGAPS\tMAX GAP"
+            )?;
+            for row in summary {
+                writeln!(
+                    w,
+                    "{}\t{}\t{}\t{}\t{}ms\t{}ms\t{}ms\t{}\t{}\t{}s",
+                    row.device,
+                    row.source,
+                    row.samples,
+                    row.invalid_times,
+                    row.min_skew_ms,
+                    row.max_skew_ms,
+                    row.mean_skew_ms,
+                    row.out_of_order,
+                    row.gaps,
+                    row.max_gap_secs
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::registry;
+
+    const INPUT: &str = r#"2024-05-05 00:00:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{"logTime":1714838402000},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838402000}
+2024-05-05 00:01:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838462000}
+2024-05-05 00:10:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838452000}
+2024-04-06 02:13:42.582  [yjhy/GZYJHYEMS001/report/period]  D:[{"daValues":[[25,0,1712340713]],"dsName":"GZYJHYGW001ADC1LD0/dsDin","errCode":0,"iedName":"GZYJHYGW001ADC1","inclusion":"0x8","ts":"2024-04-06 02:11:53"}]
+"#;
+
+    #[test]
+    fn test_analyzer() {
+        let registry = registry();
+        let mut analyzer = Analyzer::new(AnalysisConfig::default());
+        let records =
+            iot_log_parser::LogRecords::new(INPUT.as_bytes(), |line| registry.parse_log(line));
+        for record in records {
+            analyzer.observe(&record.unwrap());
+        }
+
+        let summary = analyzer.summary();
+        assert_eq!(summary.len(), 3);
+
+        let timestamp = summary
+            .iter()
+            .find(|row| row.source == "timestamp")
+            .unwrap();
+        assert_eq!(timestamp.device, "CN-18b08cf88f1");
+        assert_eq!(timestamp.samples, 3);
+        assert_eq!(timestamp.max_skew_ms, -1294);
+        assert_eq!(timestamp.min_skew_ms, -551294);
+        assert_eq!(timestamp.out_of_order, 1);
+        assert_eq!((timestamp.gaps, timestamp.max_gap_secs), (1, 540));
+
+        let log_time = summary.iter().find(|row| row.source == "logTime").unwrap();
+        assert_eq!(log_time.samples, 1);
+
+        let ts = summary.iter().find(|row| row.source == "ts").unwrap();
+        assert_eq!(
+            (ts.device.as_str(), ts.mean_skew_ms),
+            ("GZYJHYGW001ADC1", -109582)
+        );
+
+        assert_eq!(analyzer.gaps().len(), 1);
+        assert_eq!(
+            analyzer.gaps()[0].to - analyzer.gaps()[0].from,
+            TimeDelta::minutes(9)
+        );
+    }
+
+    #[test]
+    fn test_log_time_and_trailing_gap() {
+        let input = r#"2024-05-05 00:00:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{"logTime":"2024-05-05 00:00:02"},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838402000}
+2024-05-05 00:01:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{"logTime":"yesterday"},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838462000}
+2024-05-05 00:04:03.294  [byts/EVENT/CN18b08cf88f1]  D:{"data":{"logTime":"2024-05-04T16:04:02Z"},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838642000}
+"#;
+        let registry = registry();
+        let mut analyzer = Analyzer::new(AnalysisConfig {
+            max_gap: TimeDelta::minutes(2),
+            ..AnalysisConfig::default()
+        });
+        let records =
+            iot_log_parser::LogRecords::new(input.as_bytes(), |line| registry.parse_log(line));
+        for record in records {
+            analyzer.observe(&record.unwrap());
+        }
+
+        let summary = analyzer.summary();
+        let log_time = summary.iter().find(|row| row.source == "logTime").unwrap();
+        assert_eq!((log_time.samples, log_time.invalid_times), (2, 1));
+        assert_eq!((log_time.min_skew_ms, log_time.max_skew_ms), (-1294, -1294));
+        assert!(analyzer.gaps().is_empty());
+
+        // 最后一次周期上送在 00:01:03, 输入在 00:04:03 结束
+        analyzer.finish(None);
+        assert_eq!(analyzer.gaps().len(), 1);
+        assert_eq!(analyzer.gaps()[0].source, "timestamp");
+        assert_eq!(
+            analyzer.gaps()[0].to - analyzer.gaps()[0].from,
+            TimeDelta::minutes(3)
+        );
+
+        let until = NaiveDateTime::parse_from_str("2024-05-05 00:10:00", "%Y-%m-%d %H:%M:%S");
+        let mut analyzer = Analyzer::new(AnalysisConfig::default());
+        let records =
+            iot_log_parser::LogRecords::new(input.as_bytes(), |line| registry.parse_log(line));
+        for record in records {
+            analyzer.observe(&record.unwrap());
+        }
+        analyzer.finish(Some(until.unwrap()));
+        assert_eq!(analyzer.summary()[1].max_gap_secs, 536);
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use iot_log_parser::LogRecord;
+use serde::Serialize;
+
+use crate::payload::IotPayload;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // 每行一个 JSON 对象
+    Jsonl,
+    Csv,
+    // 人工查看用的对齐表格
+    Table,
+}
+
+#[derive(Serialize)]
+struct Row<'r> {
+    file: &'r str,
+    line: usize,
+    server_time: String,
+    topic: String,
+    decoder: &'static str,
+    devices: Vec<&'r str>,
+    payload: &'r IotPayload,
+}
+
+// CSV 字段中含有逗号、引号或换行时加引号
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn write_header(format: Format, w: &mut impl Write) -> io::Result<()> {
+    match format {
+        Format::Jsonl => Ok(()),
+        Format::Csv => writeln!(w, "file,line,server_time,topic,decoder,devices,payload"),
+        Format::Table => writeln!(w, "FILE\tLINE\tSERVER TIME\tTOPIC\tDEVICES\tSUMMARY"),
+    }
+}
+
+pub fn write_record(
+    format: Format,
+    w: &mut impl Write,
+    file: &str,
+    record: &LogRecord<IotPayload>,
+) -> io::Result<()> {
+    let row = Row {
+        file,
+        line: record.line,
+        server_time: record
+            .server_time
+            .format("%Y-%m-%d %H:%M:%S%.3f")
+            .to_string(),
+        topic: record.topic.join("/"),
+        decoder: record.payload.kind(),
+        devices: record.payload.devices(),
+        payload: &record.payload,
+    };
+
+    match format {
+        Format::Jsonl => {
+            serde_json::to_writer(&mut *w, &row)?;
+            writeln!(w)
+        }
+        Format::Csv => {
+            let payload = serde_json::to_string(row.payload)?;
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{}",
+                csv_field(row.file),
+                row.line,
+                row.server_time,
+                csv_field(&row.topic),
+                row.decoder,
+                csv_field(&row.devices.join(";")),
+                csv_field(&payload)
+            )
+        }
+        Format::Table => writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            row.file,
+            row.line,
+            row.server_time,
+            row.topic,
+            row.devices.join(","),
+            row.payload.summary()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use log_byts::Byts;
+
+    fn record() -> LogRecord<IotPayload> {
+        LogRecord {
+            line: 3,
+            server_time: NaiveDate::from_ymd_opt(2024, 5, 5)
+                .and_then(|date| date.and_hms_milli_opt(0, 0, 3, 294))
+                .unwrap(),
+            topic: vec!["byts".into(), "DATA".into(), "CN18b08cf88f1".into()],
+            payload: IotPayload::Byts(Byts {
+                data: serde_json::json!({ "3880": "50" }),
+                data_type: 1,
+                device_key: "CN-18b08cf88f1".into(),
+                device_sn: "CN-18b08cf88f1".into(),
+                product_key: "8sffV8oVNAd".into(),
+                timestamp: 1714838402000,
+            }),
+        }
+    }
+
+    fn render(format: Format) -> String {
+        let mut out = Vec::new();
+        write_record(format, &mut out, "gw.log", &record()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_record() {
+        let json: serde_json::Value = serde_json::from_str(&render(Format::Jsonl)).unwrap();
+        assert_eq!(json["line"], 3);
+        assert_eq!(json["topic"], "byts/DATA/CN18b08cf88f1");
+        assert_eq!(json["devices"][0], "CN-18b08cf88f1");
+        assert_eq!(json["payload"]["data"]["3880"], "50");
+
+        assert_eq!(
+            render(Format::Csv),
+            "gw.log,3,2024-05-05 00:00:03.294,byts/DATA/CN18b08cf88f1,byts,CN-18b08cf88f1,\
+             \"{\"\"data\"\":{\"\"3880\"\":\"\"50\"\"},\"\"dataType\"\":1,\"\"deviceKey\"\":\"\"CN-18b08cf88f1\"\",\
+             \"\"deviceSn\"\":\"\"CN-18b08cf88f1\"\",\"\"productKey\"\":\"\"8sffV8oVNAd\"\",\"\"timestamp\"\":1714838402000}\"\n"
+        );
+        assert_eq!(
+            render(Format::Table),
+            "gw.log\t3\t2024-05-05 00:00:03.294\tbyts/DATA/CN18b08cf88f1\tCN-18b08cf88f1\t1 points\n"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+
+use iot_log_parser::{DecoderRegistry, LogEnvelope, LogError, parse_log_with};
+use iot_parser::{Yjhy, YjhyDecoder};
+use log_byts::{Byts, BytsDecoder};
+use log_zjkg::{Zjkg, ZjkgDecoder};
+use nom::IResult;
+use serde::Serialize;
+use serde_json::Value;
+
+// 混合日志中的各类荷载
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum IotPayload {
+    Yjhy(Vec<Yjhy>),
+    Byts(Byts),
+    Zjkg(Zjkg),
+}
+
+impl IotPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IotPayload::Yjhy(_) => "yjhy",
+            IotPayload::Byts(_) => "byts",
+            IotPayload::Zjkg(_) => "zjkg",
+        }
+    }
+
+    // 设备标识: yjhy 为 iedName, byts 为 deviceKey, zjkg 为 mid
+    pub fn devices(&self) -> Vec<&str> {
+        match self {
+            IotPayload::Yjhy(yjhys) => {
+                let devices: BTreeSet<&str> = yjhys.iter().map(|y| y.ied_name.as_str()).collect();
+                devices.into_iter().collect()
+            }
+            IotPayload::Byts(byts) => vec![byts.device_key.as_str()],
+            IotPayload::Zjkg(zjkg) => vec![zjkg.mid.as_str()],
+        }
+    }
+
+    // 表格输出中的简短描述
+    pub fn summary(&self) -> String {
+        match self {
+            IotPayload::Yjhy(yjhys) => {
+                let values: usize = yjhys.iter().map(|y| value_count(&y.da_values)).sum();
+                format!("{} reports, {values} values", yjhys.len())
+            }
+            IotPayload::Byts(byts) => {
+                let points = byts.data.as_object().map_or(0, |data| data.len());
+                format!("{points} points")
+            }
+            IotPayload::Zjkg(zjkg) => {
+                let tags: usize = zjkg
+                    .images
+                    .iter()
+                    .map(|image| image.tags.as_object().map_or(0, |tags| tags.len()))
+                    .sum();
+                format!("{} images, {tags} tags", zjkg.images.len())
+            }
+        }
+    }
+}
+
+// daValues 可能是按引用名的对象, ai/change 和 period 上送的是数组
+fn value_count(values: &Value) -> usize {
+    match values {
+        Value::Object(object) => object.len(),
+        Value::Array(array) => array.len(),
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Decoder {
+    Yjhy,
+    Byts,
+    Zjkg,
+    // 按 topic 自动选择
+    Auto,
+}
+
+// 指定解码器时忽略 topic, 所有行都按该格式解码
+pub fn parse_line<'a>(
+    decoder: Decoder,
+    registry: &DecoderRegistry<IotPayload>,
+    input: &'a str,
+) -> IResult<&'a str, LogEnvelope<'a, IotPayload>, LogError<'a>> {
+    let (rest, envelope) = match decoder {
+        Decoder::Auto => return registry.parse_log(input),
+        Decoder::Yjhy => {
+            let (rest, envelope) = parse_log_with(&YjhyDecoder::new(), input)?;
+            (rest, envelope.map(IotPayload::Yjhy))
+        }
+        Decoder::Byts => {
+            let (rest, envelope) = parse_log_with(&BytsDecoder::new(), input)?;
+            (rest, envelope.map(IotPayload::Byts))
+        }
+        Decoder::Zjkg => {
+            let (rest, envelope) = parse_log_with(&ZjkgDecoder::new(), input)?;
+            (rest, envelope.map(IotPayload::Zjkg))
+        }
+    };
+    Ok((rest, envelope))
+}
+
+pub fn registry() -> DecoderRegistry<IotPayload> {
+    DecoderRegistry::new()
+        .register("yjhy", YjhyDecoder::new(), IotPayload::Yjhy)
+        .register("byts", BytsDecoder::new(), IotPayload::Byts)
+        .register("zjkg", ZjkgDecoder::new(), IotPayload::Zjkg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_log() {
+        let input = r#"2024-04-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/change]  D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]
+2024-05-05 00:00:03.294  [byts/DATA/CN18b08cf88f1]  D:{"data":{"3880":"50"},"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838402000}
+2024-05-05 00:00:21.525  [zjkg]  D:{"ver":211,"mid":"pack2","nm":"pack2","images":[{"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_2_ele_u":672.4}}]}
+"#;
+        let registry = registry();
+
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert!(matches!(envelope.payload, IotPayload::Yjhy(_)));
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert!(matches!(envelope.payload, IotPayload::Byts(_)));
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert!(matches!(envelope.payload, IotPayload::Zjkg(_)));
+        assert_eq!(input, "");
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let input = r#"2024-05-05 00:00:21.525  [zjkg]  D:{"ver":211,"mid":"pack2","nm":"pack2","images":[{"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_2_ele_u":672.4}}]}"#;
+        let registry = registry();
+
+        let (_, envelope) = parse_line(Decoder::Auto, &registry, input).unwrap();
+        assert_eq!(envelope.payload.kind(), "zjkg");
+        assert_eq!(envelope.payload.devices(), ["pack2"]);
+        assert_eq!(envelope.payload.summary(), "1 images, 1 tags");
+
+        let (_, envelope) = parse_line(Decoder::Zjkg, &registry, input).unwrap();
+        assert_eq!(envelope.payload.kind(), "zjkg");
+        assert!(matches!(
+            parse_line(Decoder::Byts, &registry, input),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_yjhy_summary() {
+        let input = r#"2024-04-06 02:10:01.773  [yjhy/GZYJHYEMS001/report/ai/change]  D:[{"daValues":[[74187.4,0,1712340586],[105,0,1712340586]],"dsName":"dsAin","errCode":0,"iedName":"BMS1","ts":"2024-04-06 02:09:46"},{"daValues":{"PCS1/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"PCS1","ts":"2024-04-06 02:10:07"},{"daValues":[[1,0,1712340586]],"dsName":"dsPara","errCode":0,"iedName":"BMS1","ts":"2024-04-06 02:09:46"}]"#;
+        let (_, envelope) = parse_line(Decoder::Auto, &registry(), input).unwrap();
+        assert_eq!(envelope.payload.summary(), "3 reports, 4 values");
+        assert_eq!(envelope.payload.devices(), ["BMS1", "PCS1"]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::BufRead;
+use std::marker::PhantomData;
+
+use nom::IResult;
+use serde::de::DeserializeOwned;
+
+use crate::{LogEnvelope, LogError, LogRecords, parse_envelope};
+
+// 荷载解码器, 每个设备族提供一个
+pub trait PayloadDecoder {
+    type Output;
+
+    fn decode(&self, topic: &[&str], json: &str) -> Result<Self::Output, serde_json::Error>;
+}
+
+// 直接用 serde 反序列化荷载的解码器
+pub struct JsonDecoder<T>(PhantomData<T>);
+
+impl<T> JsonDecoder<T> {
+    pub fn new() -> Self {
+        JsonDecoder(PhantomData)
+    }
+}
+
+impl<T> Default for JsonDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> PayloadDecoder for JsonDecoder<T> {
+    type Output = T;
+
+    fn decode(&self, _topic: &[&str], json: &str) -> Result<T, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+// 用指定的解码器解析一行日志, 荷载解码失败时返回 nom::Err::Failure
+pub fn parse_log_with<'a, D: PayloadDecoder>(
+    decoder: &D,
+    input: &'a str,
+) -> IResult<&'a str, LogEnvelope<'a, D::Output>, LogError<'a>> {
+    let (rest, envelope) = parse_envelope(input)?;
+    match decoder.decode(&envelope.topic, envelope.payload) {
+        Ok(payload) => Ok((rest, envelope.map(|_| payload))),
+        Err(e) => Err(nom::Err::Failure(
+            LogError::payload(envelope.payload, &e).locate(input),
+        )),
+    }
+}
+
+type DecodeFn<P> = Box<dyn Fn(&[&str], &str) -> Result<P, serde_json::Error>>;
+
+// 按 topic 前缀分发到不同解码器, 解码结果统一包装为 P
+pub struct DecoderRegistry<P> {
+    decoders: Vec<(Vec<String>, DecodeFn<P>)>,
+}
+
+impl<P> Default for DecoderRegistry<P> {
+    fn default() -> Self {
+        DecoderRegistry {
+            decoders: Vec::new(),
+        }
+    }
+}
+
+impl<P> DecoderRegistry<P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // prefix 按 `/` 分段匹配 topic, 例如 "yjhy" 或 "byts/DATA"
+    pub fn register<D>(
+        mut self,
+        prefix: &str,
+        decoder: D,
+        wrap: impl Fn(D::Output) -> P + 'static,
+    ) -> Self
+    where
+        D: PayloadDecoder + 'static,
+    {
+        let prefix = prefix.split('/').map(String::from).collect();
+        let decode: DecodeFn<P> =
+            Box::new(move |topic, json| decoder.decode(topic, json).map(&wrap));
+        self.decoders.push((prefix, decode));
+        self
+    }
+
+    // 最长前缀优先
+    fn find(&self, topic: &[&str]) -> Option<&DecodeFn<P>> {
+        self.decoders
+            .iter()
+            .filter(|(prefix, _)| {
+                prefix.len() <= topic.len() && prefix.iter().zip(topic).all(|(p, t)| p == t)
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, decode)| decode)
+    }
+
+    pub fn parse_log<'a>(
+        &self,
+        input: &'a str,
+    ) -> IResult<&'a str, LogEnvelope<'a, P>, LogError<'a>> {
+        let (rest, envelope) = parse_envelope(input)?;
+        let Some(decode) = self.find(&envelope.topic) else {
+            return Err(nom::Err::Failure(
+                LogError::unknown_topic(input, &envelope.topic).locate(input),
+            ));
+        };
+        match decode(&envelope.topic, envelope.payload) {
+            Ok(payload) => Ok((rest, envelope.map(|_| payload))),
+            Err(e) => Err(nom::Err::Failure(
+                LogError::payload(envelope.payload, &e).locate(input),
+            )),
+        }
+    }
+
+    // 逐行读取混合日志
+    pub fn records<R: BufRead>(&self, reader: R) -> LogRecords<'_, R, P> {
+        LogRecords::new(reader, |line| self.parse_log(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogErrorKind;
+    use serde::Deserialize;
+    use serde_json::error::Category;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Ver {
+        ver: u8,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Payload {
+        Ver(Ver),
+        Raw(serde_json::Value),
+    }
+
+    fn registry() -> DecoderRegistry<Payload> {
+        DecoderRegistry::new()
+            .register("zjkg", JsonDecoder::<Ver>::new(), Payload::Ver)
+            .register("byts", JsonDecoder::new(), Payload::Raw)
+            .register("byts/DATA", JsonDecoder::<Ver>::new(), Payload::Ver)
+    }
+
+    #[test]
+    fn test_parse_log_with() {
+        let input = r#"2024-05-05 00:00:21.525  [zjkg]  D:{"ver":211}"#;
+        let (_, envelope) = parse_log_with(&JsonDecoder::<Ver>::new(), input).unwrap();
+        assert_eq!(envelope.payload, Ver { ver: 211 });
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = registry();
+        let input = "2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":211}\n\
+                     2024-05-05 00:00:03.294  [byts/DATA/CN18b08cf88f1]  D:{\"ver\":1}\n\
+                     2024-05-05 00:00:03.294  [byts/EVENT/CN18b08cf88f1]  D:{\"ver\":2}\n";
+
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert_eq!(envelope.payload, Payload::Ver(Ver { ver: 211 }));
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert_eq!(envelope.payload, Payload::Ver(Ver { ver: 1 }));
+        let (input, envelope) = registry.parse_log(input).unwrap();
+        assert_eq!(
+            envelope.payload,
+            Payload::Raw(serde_json::json!({"ver": 2}))
+        );
+        assert_eq!(input, "");
+    }
+
+    #[test]
+    fn test_registry_errors() {
+        let registry = registry();
+
+        let input = r#"2024-05-05 00:00:21.525  [yjhy/GZYJHYEMS001/report/change]  D:[]"#;
+        let Err(nom::Err::Failure(err)) = registry.parse_log(input) else {
+            panic!("expected an unknown topic error");
+        };
+        assert_eq!(
+            err.kind,
+            LogErrorKind::UnknownTopic("yjhy/GZYJHYEMS001/report/change".into())
+        );
+
+        let input = r#"2024-05-05 00:00:21.525  zjkg  D:{"ver":211}"#;
+        let Err(nom::Err::Error(err)) = registry.parse_log(input) else {
+            panic!("expected an envelope error");
+        };
+        assert!(err.is_envelope());
+    }
+
+    #[test]
+    fn test_payload_error() {
+        let input = "2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":211}\n\
+                     2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":\"211\"}";
+        let (rest, _) = parse_log_with(&JsonDecoder::<Ver>::new(), input).unwrap();
+        let Err(nom::Err::Failure(err)) = parse_log_with(&JsonDecoder::<Ver>::new(), rest) else {
+            panic!("expected a payload error");
+        };
+        assert!(err.is_payload());
+        assert_eq!((err.line, err.offset), (1, 35));
+        let LogErrorKind::Payload {
+            category,
+            column,
+            message,
+        } = &err.kind
+        else {
+            panic!("expected a payload error");
+        };
+        assert_eq!(*category, Category::Data);
+        assert_eq!(*column, 12);
+        assert!(message.starts_with("invalid type: string \"211\""));
+
+        // 行号和偏移相对于整个输入
+        let err = err.locate(input);
+        assert_eq!((err.line, err.offset), (2, 82));
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use nom::IResult;
+use nom::bytes::complete::{tag, take_while_m_n};
+use nom::character::complete::{i32, newline, not_line_ending, space1, u32};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::many0;
+use nom::sequence::tuple;
+
+use crate::{LogError, parse_topic_name};
+
+// 带服务器时间和 topic 的日志记录
+#[derive(Debug, PartialEq)]
+pub struct LogEnvelope<'a, T> {
+    pub server_time: NaiveDateTime,
+    pub topic: Vec<&'a str>,
+    pub payload: T,
+}
+
+impl<'a, T> LogEnvelope<'a, T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> LogEnvelope<'a, U> {
+        LogEnvelope {
+            server_time: self.server_time,
+            topic: self.topic,
+            payload: f(self.payload),
+        }
+    }
+}
+
+// 解析服务器时间, 精确到毫秒
+pub fn parse_server_time(input: &str) -> IResult<&str, NaiveDateTime, LogError<'_>> {
+    let mut parser = tuple((
+        i32,
+        tag("-"),
+        u32,
+        tag("-"),
+        u32,
+        space1,
+        u32,
+        tag(":"),
+        u32,
+        tag(":"),
+        u32,
+        tag("."),
+        take_while_m_n(3, 3, |c: char| c.is_ascii_digit()),
+    ));
+    let (rest, (year, _, month, _, day, _, hour, _, minute, _, second, _, milli)) = parser(input)?;
+    let milli = milli.parse::<u32>().unwrap_or_default();
+    let server_time = NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_milli_opt(hour, minute, second, milli))
+        .ok_or(nom::Err::Error(LogError::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        )))?;
+    Ok((rest, server_time))
+}
+
+// 解析 `D:` 之后的 JSON 荷载, 原样返回
+pub fn parse_payload(input: &str) -> IResult<&str, &str, LogError<'_>> {
+    let mut parser = tuple((tag("D:"), not_line_ending));
+    let (input, (_, json)) = parser(input)?;
+    Ok((input, json))
+}
+
+// 解析日志外层, 荷载保持为 JSON 字符串
+pub fn parse_envelope(input: &str) -> IResult<&str, LogEnvelope<'_, &str>, LogError<'_>> {
+    let mut parser = tuple((
+        parse_server_time,
+        space1,
+        parse_topic_name,
+        space1,
+        parse_payload,
+        many0(newline),
+    ));
+    let (rest, (server_time, _, topic, _, payload, _)) =
+        parser(input).map_err(|e| e.map(|err| err.locate(input)))?;
+    Ok((
+        rest,
+        LogEnvelope {
+            server_time,
+            topic,
+            payload,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogErrorKind;
+
+    #[test]
+    fn test_server_time() {
+        let input = "2024-05-05 00:00:03.294";
+        let expected = NaiveDate::from_ymd_opt(2024, 5, 5)
+            .and_then(|date| date.and_hms_milli_opt(0, 0, 3, 294))
+            .unwrap();
+        assert_eq!(parse_server_time(input), Ok(("", expected)));
+
+        // 不合法的日期
+        assert!(parse_server_time("2024-13-05 00:00:03.294").is_err());
+        assert!(parse_server_time("2024-02-30 00:00:03.294").is_err());
+        assert!(parse_server_time("2024-05-05 24:00:03.294").is_err());
+    }
+
+    #[test]
+    fn test_envelope() {
+        let input = "2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":211}\n2024";
+        let (rest, envelope) = parse_envelope(input).unwrap();
+        assert_eq!(rest, "2024");
+        assert_eq!(envelope.topic, vec!["zjkg"]);
+        assert_eq!(envelope.payload, "{\"ver\":211}");
+    }
+
+    #[test]
+    fn test_envelope_error() {
+        let input = "2024-13-05 00:00:21.525  [zjkg]  D:{}";
+        let Err(nom::Err::Error(err)) = parse_envelope(input) else {
+            panic!("expected an envelope error");
+        };
+        assert_eq!(err.kind, LogErrorKind::Envelope(ErrorKind::Verify));
+        assert_eq!((err.line, err.offset), (1, 0));
+
+        let input = "2024-05-05 00:00:21.525  zjkg  D:{}";
+        let Err(nom::Err::Error(err)) = parse_envelope(input) else {
+            panic!("expected an envelope error");
+        };
+        assert!(err.is_envelope());
+        assert_eq!((err.line, err.offset), (1, 25));
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use nom::Offset;
+use nom::error::{ErrorKind, ParseError};
+use serde_json::error::Category;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogErrorKind {
+    // 日志外层 (服务器时间 / topic / `D:`) 格式错误
+    Envelope(ErrorKind),
+    // 没有与 topic 匹配的解码器
+    UnknownTopic(String),
+    // 荷载 JSON 无法解码为目标结构, column 为 JSON 内的列号
+    Payload {
+        category: Category,
+        column: usize,
+        message: String,
+    },
+}
+
+// 日志解析错误, line 和 offset 相对于传给解析函数的 input
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogError<'a> {
+    // 出错位置开始的剩余输入, 荷载错误时为 `D:` 之后的 JSON
+    pub input: &'a str,
+    // 出错所在行, 从 1 开始
+    pub line: usize,
+    // 出错位置的字节偏移, 荷载错误时为 JSON 起始位置
+    pub offset: usize,
+    pub kind: LogErrorKind,
+}
+
+impl<'a> LogError<'a> {
+    pub fn payload(json: &'a str, err: &serde_json::Error) -> Self {
+        LogError {
+            input: json,
+            line: 0,
+            offset: 0,
+            kind: LogErrorKind::Payload {
+                category: err.classify(),
+                column: err.column(),
+                message: err.to_string(),
+            },
+        }
+    }
+
+    pub fn unknown_topic(input: &'a str, topic: &[&str]) -> Self {
+        LogError {
+            input,
+            line: 0,
+            offset: 0,
+            kind: LogErrorKind::UnknownTopic(topic.join("/")),
+        }
+    }
+
+    // 根据原始输入计算行号和偏移, self.input 必须是 source 的一部分
+    pub fn locate(mut self, source: &str) -> Self {
+        self.offset = source.offset(self.input);
+        self.line = source[..self.offset].matches('\n').count() + 1;
+        self
+    }
+
+    pub fn is_envelope(&self) -> bool {
+        matches!(self.kind, LogErrorKind::Envelope(_))
+    }
+
+    pub fn is_payload(&self) -> bool {
+        matches!(self.kind, LogErrorKind::Payload { .. })
+    }
+}
+
+impl<'a> ParseError<&'a str> for LogError<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        LogError {
+            input,
+            line: 0,
+            offset: 0,
+            kind: LogErrorKind::Envelope(kind),
+        }
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl fmt::Display for LogError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            LogErrorKind::Envelope(kind) => write!(
+                f,
+                "line {}, offset {}: malformed envelope ({})",
+                self.line,
+                self.offset,
+                kind.description()
+            ),
+            LogErrorKind::UnknownTopic(topic) => write!(
+                f,
+                "line {}, offset {}: no decoder for topic `{topic}`",
+                self.line, self.offset
+            ),
+            LogErrorKind::Payload {
+                column, message, ..
+            } => write!(
+                f,
+                "line {}, offset {}, payload column {column}: {message}",
+                self.line, self.offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LogError<'_> {}
@@ -0,0 +1,11 @@
+mod decoder;
+mod envelope;
+mod error;
+mod reader;
+mod topic;
+
+pub use decoder::*;
+pub use envelope::*;
+pub use error::*;
+pub use reader::*;
+pub use topic::*;
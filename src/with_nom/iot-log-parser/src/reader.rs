@@ -0,0 +1,234 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use flate2::bufread::MultiGzDecoder;
+use nom::IResult;
+
+use crate::{LogEnvelope, LogError, LogErrorKind};
+
+// 从流中读出的一条记录, topic 已复制为自有字符串
+#[derive(Debug, PartialEq)]
+pub struct LogRecord<T> {
+    pub line: usize,
+    pub server_time: NaiveDateTime,
+    pub topic: Vec<String>,
+    pub payload: T,
+}
+
+#[derive(Debug)]
+pub enum RecordErrorKind {
+    Io(io::Error),
+    InvalidUtf8,
+    Log { offset: usize, kind: LogErrorKind },
+}
+
+// 单行的读取或解析错误, 不会中断后续行的读取 (Io 错误除外)
+#[derive(Debug)]
+pub struct RecordError {
+    pub line: usize,
+    pub kind: RecordErrorKind,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RecordErrorKind::Io(err) => write!(f, "line {}: {err}", self.line),
+            RecordErrorKind::InvalidUtf8 => write!(f, "line {}: invalid UTF-8", self.line),
+            RecordErrorKind::Log { offset, kind } => {
+                let err = LogError {
+                    input: "",
+                    line: self.line,
+                    offset: *offset,
+                    kind: kind.clone(),
+                };
+                write!(f, "{err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+type LineParser<'p, T> =
+    Box<dyn for<'a> Fn(&'a str) -> IResult<&'a str, LogEnvelope<'a, T>, LogError<'a>> + 'p>;
+
+// 逐行解析日志的迭代器, 只持有一行的缓冲区
+pub struct LogRecords<'p, R, T> {
+    reader: R,
+    parse: LineParser<'p, T>,
+    buf: Vec<u8>,
+    line: usize,
+    done: bool,
+}
+
+impl<'p, R: BufRead, T> LogRecords<'p, R, T> {
+    pub fn new(
+        reader: R,
+        parse: impl for<'a> Fn(&'a str) -> IResult<&'a str, LogEnvelope<'a, T>, LogError<'a>> + 'p,
+    ) -> Self {
+        LogRecords {
+            reader,
+            parse: Box::new(parse),
+            buf: Vec::new(),
+            line: 0,
+            done: false,
+        }
+    }
+
+    fn parse_line(&self) -> Result<LogRecord<T>, RecordErrorKind> {
+        let text = std::str::from_utf8(&self.buf).map_err(|_| RecordErrorKind::InvalidUtf8)?;
+        let text = text.trim_end_matches(['\r', '\n']);
+        match (self.parse)(text) {
+            Ok((_, envelope)) => Ok(LogRecord {
+                line: self.line,
+                server_time: envelope.server_time,
+                topic: envelope.topic.into_iter().map(String::from).collect(),
+                payload: envelope.payload,
+            }),
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(RecordErrorKind::Log {
+                offset: err.offset,
+                kind: err.kind,
+            }),
+            Err(nom::Err::Incomplete(_)) => Err(RecordErrorKind::Log {
+                offset: text.len(),
+                kind: LogErrorKind::Envelope(nom::error::ErrorKind::Eof),
+            }),
+        }
+    }
+}
+
+impl<R: BufRead, T> Iterator for LogRecords<'_, R, T> {
+    type Item = Result<LogRecord<T>, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buf.clear();
+            self.line += 1;
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(_) if self.buf.trim_ascii().is_empty() => continue,
+                Ok(_) => {
+                    return Some(self.parse_line().map_err(|kind| RecordError {
+                        line: self.line,
+                        kind,
+                    }));
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(RecordError {
+                        line: self.line,
+                        kind: RecordErrorKind::Io(err),
+                    }));
+                }
+            }
+        }
+        None
+    }
+}
+
+// 如果流以 gzip 魔数开头则自动解压, 支持多段拼接的 .gz 文件
+pub fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn open_log(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonDecoder, parse_log_with};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use serde::Deserialize;
+    use std::io::{Cursor, Write};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Ver {
+        ver: u8,
+    }
+
+    const INPUT: &str = "2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":211}\r\n\
+                         \n\
+                         2024-13-05 00:00:21.525  [zjkg]  D:{\"ver\":1}\n\
+                         2024-05-05 00:00:22.525  [zjkg]  D:{\"ver\":\"2\"}\n\
+                         2024-05-05 00:00:23.525  [zjkg]  D:{\"ver\":3}";
+
+    fn parse(input: &str) -> IResult<&str, LogEnvelope<'_, Ver>, LogError<'_>> {
+        parse_log_with(&JsonDecoder::new(), input)
+    }
+
+    fn check(records: Vec<Result<LogRecord<Ver>, RecordError>>) {
+        assert_eq!(records.len(), 4);
+
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.line, 1);
+        assert_eq!(record.topic, vec!["zjkg".to_string()]);
+        assert_eq!(record.payload, Ver { ver: 211 });
+
+        let err = records[1].as_ref().unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(matches!(
+            err.kind,
+            RecordErrorKind::Log {
+                kind: LogErrorKind::Envelope(_),
+                ..
+            }
+        ));
+
+        let err = records[2].as_ref().unwrap_err();
+        assert_eq!(err.line, 4);
+        assert!(matches!(
+            err.kind,
+            RecordErrorKind::Log {
+                offset: 35,
+                kind: LogErrorKind::Payload { .. },
+            }
+        ));
+
+        let record = records[3].as_ref().unwrap();
+        assert_eq!(record.line, 5);
+        assert_eq!(record.payload, Ver { ver: 3 });
+    }
+
+    #[test]
+    fn test_log_records() {
+        let records = LogRecords::new(Cursor::new(INPUT), parse).collect();
+        check(records);
+    }
+
+    #[test]
+    fn test_gzip_records() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let reader = decompress(Cursor::new(compressed)).unwrap();
+        let records = LogRecords::new(reader, parse).collect();
+        check(records);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let input = b"2024-05-05 00:00:21.525  [zjkg]  D:{\"ver\":\xff}\n\
+                      2024-05-05 00:00:23.525  [zjkg]  D:{\"ver\":3}";
+        let records: Vec<_> = LogRecords::new(Cursor::new(&input[..]), parse).collect();
+        assert!(matches!(
+            records[0],
+            Err(RecordError {
+                line: 1,
+                kind: RecordErrorKind::InvalidUtf8
+            })
+        ));
+        assert!(records[1].is_ok());
+    }
+}
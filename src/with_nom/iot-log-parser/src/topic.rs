@@ -0,0 +1,200 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while};
+use nom::combinator::{all_consuming, map, verify};
+use nom::multi::separated_list1;
+use nom::sequence::tuple;
+
+use crate::LogError;
+
+// topic 层级中允许的字符: 除了分隔符、通配符和日志中的 `]`
+fn is_level_char(c: char) -> bool {
+    !matches!(c, '/' | '+' | '#' | ']' | '\0' | '\n' | '\r')
+}
+
+fn parse_level(input: &str) -> IResult<&str, &str, LogError<'_>> {
+    take_while(is_level_char)(input)
+}
+
+// MQTT topic 名, 允许空层级, 但整个 topic 不能为空
+pub fn parse_topic(input: &str) -> IResult<&str, Vec<&str>, LogError<'_>> {
+    verify(
+        separated_list1(tag("/"), parse_level),
+        |levels: &[&str]| levels != [""],
+    )(input)
+}
+
+pub fn parse_topic_name(input: &str) -> IResult<&str, Vec<&str>, LogError<'_>> {
+    let mut parser = tuple((tag("["), parse_topic, tag("]")));
+    let (input, (_, topics, _)) = parser(input)?;
+    Ok((input, topics))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterLevel {
+    Exact(String),
+    // `+`, 匹配单个层级
+    Single,
+    // `#`, 匹配剩余所有层级, 只能出现在最后
+    Multi,
+}
+
+// MQTT 订阅过滤器, 例如 `yjhy/+/report/#`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicFilter {
+    levels: Vec<FilterLevel>,
+}
+
+fn parse_filter_level(input: &str) -> IResult<&str, FilterLevel> {
+    alt((
+        map(tag("+"), |_| FilterLevel::Single),
+        map(tag("#"), |_| FilterLevel::Multi),
+        map(take_while(is_level_char), |level: &str| {
+            FilterLevel::Exact(level.into())
+        }),
+    ))(input)
+}
+
+fn parse_topic_filter(input: &str) -> IResult<&str, TopicFilter> {
+    let levels = verify(
+        separated_list1(tag("/"), parse_filter_level),
+        |levels: &[FilterLevel]| {
+            let multi = levels.iter().position(|level| *level == FilterLevel::Multi);
+            multi.is_none_or(|at| at == levels.len() - 1)
+                && levels != [FilterLevel::Exact(String::new())]
+        },
+    );
+    let (input, levels) = all_consuming(levels)(input)?;
+    Ok((input, TopicFilter { levels }))
+}
+
+impl FromStr for TopicFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_topic_filter(s)
+            .map(|(_, filter)| filter)
+            .map_err(|_| format!("invalid topic filter `{s}`"))
+    }
+}
+
+impl fmt::Display for TopicFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels: Vec<&str> = self
+            .levels
+            .iter()
+            .map(|level| match level {
+                FilterLevel::Exact(level) => level.as_str(),
+                FilterLevel::Single => "+",
+                FilterLevel::Multi => "#",
+            })
+            .collect();
+        write!(f, "{}", levels.join("/"))
+    }
+}
+
+impl TopicFilter {
+    pub fn levels(&self) -> &[FilterLevel] {
+        &self.levels
+    }
+
+    pub fn matches<S: AsRef<str>>(&self, topic: &[S]) -> bool {
+        // 以 `$` 开头的系统 topic 不被首层通配符匹配
+        if topic
+            .first()
+            .is_some_and(|level| level.as_ref().starts_with('$'))
+            && !matches!(self.levels.first(), Some(FilterLevel::Exact(_)))
+        {
+            return false;
+        }
+
+        let mut topic = topic.iter();
+        for level in &self.levels {
+            match (level, topic.next()) {
+                (FilterLevel::Multi, _) => return true,
+                (FilterLevel::Single, Some(_)) => {}
+                (FilterLevel::Exact(expected), Some(actual)) if expected == actual.as_ref() => {}
+                _ => return false,
+            }
+        }
+        topic.next().is_none()
+    }
+}
+
+// 任意一个过滤器匹配即可, 过滤器为空时全部匹配
+pub fn matches_any<S: AsRef<str>>(filters: &[TopicFilter], topic: &[S]) -> bool {
+    filters.is_empty() || filters.iter().any(|filter| filter.matches(topic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_name() {
+        assert_eq!(
+            parse_topic_name("[yjhy/GZYJHYEMS001/report/ai/change]"),
+            Ok(("", vec!["yjhy", "GZYJHYEMS001", "report", "ai", "change"]))
+        );
+        assert_eq!(
+            parse_topic_name("[byts/DATA/CN-18b08cf88f1]"),
+            Ok(("", vec!["byts", "DATA", "CN-18b08cf88f1"]))
+        );
+        assert_eq!(
+            parse_topic_name("[/site_1//v1.2]"),
+            Ok(("", vec!["", "site_1", "", "v1.2"]))
+        );
+        assert!(parse_topic_name("[]").is_err());
+        assert!(parse_topic_name("[yjhy/+/report]").is_err());
+        assert!(parse_topic_name("[yjhy/#]").is_err());
+    }
+
+    #[test]
+    fn test_topic_filter() {
+        let filter: TopicFilter = "yjhy/+/report/#".parse().unwrap();
+        assert_eq!(
+            filter.levels(),
+            [
+                FilterLevel::Exact("yjhy".into()),
+                FilterLevel::Single,
+                FilterLevel::Exact("report".into()),
+                FilterLevel::Multi,
+            ]
+        );
+        assert_eq!(filter.to_string(), "yjhy/+/report/#");
+
+        assert!("#".parse::<TopicFilter>().is_ok());
+        assert!("a//b".parse::<TopicFilter>().is_ok());
+        assert!("yjhy/#/report".parse::<TopicFilter>().is_err());
+        assert!("yjhy/re#".parse::<TopicFilter>().is_err());
+        assert!("yjhy/a+".parse::<TopicFilter>().is_err());
+        assert!("".parse::<TopicFilter>().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let filter: TopicFilter = "yjhy/+/report/#".parse().unwrap();
+        assert!(filter.matches(&["yjhy", "GZYJHYEMS001", "report", "change"]));
+        assert!(filter.matches(&["yjhy", "GZYJHYEMS001", "report", "ai", "change"]));
+        // `#` 也匹配父层级
+        assert!(filter.matches(&["yjhy", "GZYJHYEMS001", "report"]));
+        assert!(!filter.matches(&["yjhy", "GZYJHYEMS001"]));
+        assert!(!filter.matches(&["byts", "DATA", "CN18b08cf88f1"]));
+
+        let filter: TopicFilter = "byts/+".parse().unwrap();
+        assert!(!filter.matches(&["byts", "DATA", "CN18b08cf88f1"]));
+        assert!(filter.matches(&["byts", ""]));
+
+        let filter: TopicFilter = "#".parse().unwrap();
+        assert!(filter.matches(&["zjkg"]));
+        assert!(!filter.matches(&["$SYS", "broker"]));
+
+        let filters = ["zjkg".parse().unwrap(), "byts/#".parse().unwrap()];
+        assert!(matches_any(&filters, &["byts", "DATA", "CN18b08cf88f1"]));
+        assert!(!matches_any(&filters, &["yjhy", "GZYJHYEMS001"]));
+        assert!(matches_any::<&str>(&[], &["yjhy"]));
+    }
+}
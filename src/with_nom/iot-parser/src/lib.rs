@@ -1,15 +1,26 @@
-use nom::bytes::complete::tag;
-use nom::character::complete::{alphanumeric1, digit1, space1};
-use nom::character::complete::{newline, not_line_ending};
-use nom::multi::{many0, separated_list1};
-use nom::sequence::tuple;
-use nom::{IResult, Needed};
+use iot_log_parser::{JsonDecoder, parse_log_with};
+use nom::IResult;
 use num::{BigInt, Num};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::io::BufRead;
+
+pub use iot_log_parser::{
+    LogEnvelope, LogError, LogRecord, LogRecords, RecordError, decompress, open_log,
+};
+
+mod quality;
+mod reference;
+mod report;
+mod state;
+
+pub use quality::*;
+pub use reference::*;
+pub use report::*;
+pub use state::*;
 
 // untagged: https://medium.com/@dmitrydoronin/union-types-in-rust-3acf65ed849
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ErrorCode {
     StringErr(String),
@@ -27,57 +38,29 @@ pub struct Yjhy {
     pub ts: String,
 }
 
-// 解析服务器时间
-fn parse_server_time(input: &str) -> IResult<&str, &str> {
-    let mut parser = tuple((
-        digit1,
-        tag("-"),
-        digit1,
-        tag("-"),
-        digit1,
-        space1,
-        digit1,
-        tag(":"),
-        digit1,
-        tag(":"),
-        digit1,
-        tag("."),
-        digit1,
-    ));
-    let (input, (year, _, _month, _, _day, _, _hour, _, _minute, _, _seconds, _, _micro_sec)) =
-        parser(input)?;
-    Ok((input, year))
-}
+pub type YjhyDecoder = JsonDecoder<Vec<Yjhy>>;
 
-fn parse_topic_name(input: &str) -> IResult<&str, Vec<&str>> {
-    let mut parser = tuple((tag("["), separated_list1(tag("/"), alphanumeric1), tag("]")));
-    let (input, (_, topics, _)) = parser(input)?;
-    Ok((input, topics))
+pub fn parse_log(input: &str) -> IResult<&str, LogEnvelope<'_, Vec<Yjhy>>, LogError<'_>> {
+    parse_log_with(&YjhyDecoder::new(), input)
 }
 
-fn parse_json_str(input: &str) -> IResult<&str, Vec<Yjhy>> {
-    let mut parser = tuple((tag("D:"), not_line_ending));
-    let (input, (_, json)) = parser(input)?;
-
-    let parsed = serde_json::from_str::<Vec<Yjhy>>(json);
-    if let Ok(parsed) = parsed {
-        Ok((input, parsed))
-    } else {
-        Err(nom::Err::Incomplete(Needed::Unknown))
-    }
+// 逐行读取日志文件, 坏行不会中断读取
+pub fn read_logs<'p, R: BufRead>(reader: R) -> LogRecords<'p, R, Vec<Yjhy>> {
+    LogRecords::new(reader, parse_log)
 }
 
-pub fn parse_log(input: &str) -> IResult<&str, Vec<Yjhy>> {
-    let mut parser = tuple((
-        parse_server_time,
-        space1,
-        parse_topic_name,
-        space1,
-        parse_json_str,
-        many0(newline),
-    ));
-    let (input, (_ts, _, _topic_name, _, json_str, _)) = parser(input)?;
-    Ok((input, json_str))
+// 解析日志并按 topic 解码 daValues
+pub fn parse_reports(input: &str) -> Result<Vec<Report>, ReportError> {
+    let (_, envelope) = parse_log(input).map_err(|e| match e {
+        nom::Err::Error(err) | nom::Err::Failure(err) => ReportError::Log(err.to_string()),
+        nom::Err::Incomplete(_) => ReportError::Log("incomplete input".into()),
+    })?;
+    let kind = ReportKind::from_topic(&envelope.topic)?;
+    envelope
+        .payload
+        .iter()
+        .map(|yjhy| yjhy.decode(kind))
+        .collect()
 }
 
 fn hex_to_binary(hex: &str) -> String {
@@ -95,6 +78,8 @@ fn hex_to_binary(hex: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
+    use iot_log_parser::PayloadDecoder;
 
     #[test]
     fn test_hex_to_binary() {
@@ -119,27 +104,11 @@ mod tests {
         assert_eq!(hex_to_binary("xyz"), "");
     }
 
-    #[test]
-    fn test_server_time() {
-        let input = "2024-04-06 02:10:07.714";
-        assert_eq!(parse_server_time(input), Ok(("", "2024")));
-    }
-
-    #[test]
-    fn test_topic_name() {
-        let input = "[yjhy/GZYJHYEMS001/report/change]";
-        assert_eq!(
-            parse_topic_name(input),
-            Ok(("", vec!["yjhy", "GZYJHYEMS001", "report", "change"]))
-        );
-    }
-
     #[test]
     fn test_json_str() {
-        let input = r#"D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
-        if let Ok(res) = parse_json_str(input) {
-            println!("{:?}", res.1);
-        }
+        let input = r#"[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
+        let yjhys = YjhyDecoder::new().decode(&[], input).unwrap();
+        assert_eq!(yjhys[0].ied_name, "GZYJHYGW001PCS1");
     }
 
     #[test]
@@ -151,6 +120,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_log_envelope() {
+        let input = r#"2024-04-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/change]  D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
+        let (_, envelope) = parse_log(input).unwrap();
+        assert_eq!(
+            envelope.server_time,
+            NaiveDate::from_ymd_opt(2024, 4, 6)
+                .and_then(|date| date.and_hms_milli_opt(2, 10, 7, 714))
+                .unwrap()
+        );
+        assert_eq!(
+            envelope.topic,
+            vec!["yjhy", "GZYJHYEMS001", "report", "change"]
+        );
+        assert_eq!(envelope.payload.len(), 1);
+
+        let input = r#"2024-13-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/change]  D:[]"#;
+        assert!(parse_log(input).is_err());
+    }
+
     #[test]
     fn test_ai_change() {
         // 遥测变化上送
@@ -169,6 +158,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_reports() {
+        let input = r#"2024-04-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/change]  D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
+        let reports = parse_reports(input).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].ied_name, "GZYJHYGW001PCS1");
+        assert_eq!(reports[0].da_values.kind(), ReportKind::Change);
+
+        // topic 与 daValues 形状不符
+        let input = r#"2024-04-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/period]  D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
+        assert!(matches!(
+            parse_reports(input),
+            Err(ReportError::ShapeMismatch {
+                kind: ReportKind::Period,
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_inclusion() -> anyhow::Result<()> {
         let input = "0xFF8";
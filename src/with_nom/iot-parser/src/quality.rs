@@ -0,0 +1,186 @@
+use std::fmt;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+// IEC 61850-7-3 品质的有效性, 占最低两位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Validity {
+    Good,
+    Invalid,
+    Reserved,
+    Questionable,
+}
+
+impl fmt::Display for Validity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Validity::Good => write!(f, "good"),
+            Validity::Invalid => write!(f, "invalid"),
+            Validity::Reserved => write!(f, "reserved"),
+            Validity::Questionable => write!(f, "questionable"),
+        }
+    }
+}
+
+// 品质的详细标志, 值为在品质字中的位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityFlag {
+    Overflow = 1 << 2,
+    OutOfRange = 1 << 3,
+    BadReference = 1 << 4,
+    Oscillatory = 1 << 5,
+    Failure = 1 << 6,
+    OldData = 1 << 7,
+    Inconsistent = 1 << 8,
+    Inaccurate = 1 << 9,
+    // source = substituted, 否则为 process
+    Substituted = 1 << 10,
+    Test = 1 << 11,
+    OperatorBlocked = 1 << 12,
+}
+
+impl QualityFlag {
+    pub const ALL: [QualityFlag; 11] = [
+        QualityFlag::Overflow,
+        QualityFlag::OutOfRange,
+        QualityFlag::BadReference,
+        QualityFlag::Oscillatory,
+        QualityFlag::Failure,
+        QualityFlag::OldData,
+        QualityFlag::Inconsistent,
+        QualityFlag::Inaccurate,
+        QualityFlag::Substituted,
+        QualityFlag::Test,
+        QualityFlag::OperatorBlocked,
+    ];
+}
+
+impl fmt::Display for QualityFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QualityFlag::Overflow => "overflow",
+            QualityFlag::OutOfRange => "out_of_range",
+            QualityFlag::BadReference => "bad_reference",
+            QualityFlag::Oscillatory => "oscillatory",
+            QualityFlag::Failure => "failure",
+            QualityFlag::OldData => "old_data",
+            QualityFlag::Inconsistent => "inconsistent",
+            QualityFlag::Inaccurate => "inaccurate",
+            QualityFlag::Substituted => "substituted",
+            QualityFlag::Test => "test",
+            QualityFlag::OperatorBlocked => "operator_blocked",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// 网关上送的品质字, 位定义与 libiec61850 的 Quality 一致:
+// bit 0-1 validity (0 good, 1 reserved, 2 invalid, 3 questionable), bit 2-12 详细标志
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quality(u32);
+
+impl Quality {
+    pub const GOOD: Quality = Quality(0);
+
+    pub fn new(bits: u32) -> Self {
+        Quality(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn validity(self) -> Validity {
+        match self.0 & 0b11 {
+            0 => Validity::Good,
+            1 => Validity::Reserved,
+            2 => Validity::Invalid,
+            _ => Validity::Questionable,
+        }
+    }
+
+    pub fn has(self, flag: QualityFlag) -> bool {
+        self.0 & flag as u32 != 0
+    }
+
+    pub fn flags(self) -> impl Iterator<Item = QualityFlag> {
+        QualityFlag::ALL
+            .into_iter()
+            .filter(move |flag| self.has(*flag))
+    }
+
+    // 只看有效性, 取代值和检修态仍可能为 good
+    pub fn is_good(self) -> bool {
+        self.validity() == Validity::Good
+    }
+}
+
+impl From<u32> for Quality {
+    fn from(bits: u32) -> Self {
+        Quality(bits)
+    }
+}
+
+// 例如 `questionable|old_data|inaccurate`
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.validity())?;
+        for flag in self.flags() {
+            write!(f, "|{flag}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Quality {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let flags: Vec<QualityFlag> = self.flags().collect();
+        let mut quality = serializer.serialize_struct("Quality", 3)?;
+        quality.serialize_field("bits", &self.0)?;
+        quality.serialize_field("validity", &self.validity())?;
+        quality.serialize_field("flags", &flags)?;
+        quality.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality() {
+        assert_eq!(Quality::GOOD.validity(), Validity::Good);
+        assert!(Quality::GOOD.is_good());
+        assert_eq!(Quality::GOOD.to_string(), "good");
+
+        let quality = Quality::new(0b11 | 1 << 7 | 1 << 9);
+        assert_eq!(quality.validity(), Validity::Questionable);
+        assert!(!quality.is_good());
+        assert!(quality.has(QualityFlag::OldData));
+        assert!(!quality.has(QualityFlag::Failure));
+        assert_eq!(quality.to_string(), "questionable|old_data|inaccurate");
+
+        assert_eq!(Quality::new(1).validity(), Validity::Reserved);
+        assert_eq!(Quality::new(2 | 1 << 6).to_string(), "invalid|failure");
+
+        // 取代值仍然是 good
+        let quality = Quality::new(1 << 10 | 1 << 11 | 1 << 12);
+        assert!(quality.is_good());
+        assert_eq!(
+            quality.flags().collect::<Vec<_>>(),
+            [
+                QualityFlag::Substituted,
+                QualityFlag::Test,
+                QualityFlag::OperatorBlocked
+            ]
+        );
+
+        assert_eq!(
+            serde_json::to_value(Quality::new(2 | 1 << 2)).unwrap(),
+            serde_json::json!({"bits": 6, "validity": "invalid", "flags": ["overflow"]})
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nom::IResult;
+use nom::bytes::complete::{tag, take_while_m_n, take_while1};
+use nom::combinator::{eof, map_res};
+use nom::multi::many0;
+use nom::sequence::{preceded, tuple};
+use serde::Serialize;
+
+use crate::{DaValues, Report};
+
+// 功能约束 (IEC 61850-7-2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum FunctionalConstraint {
+    ST,
+    MX,
+    SP,
+    SV,
+    CF,
+    DC,
+    SG,
+    SE,
+    SR,
+    OR,
+    BL,
+    EX,
+    CO,
+    US,
+    MS,
+    RP,
+    BR,
+    LG,
+    GO,
+    GS,
+}
+
+impl FromStr for FunctionalConstraint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use FunctionalConstraint::*;
+
+        let fc = match s {
+            "ST" => ST,
+            "MX" => MX,
+            "SP" => SP,
+            "SV" => SV,
+            "CF" => CF,
+            "DC" => DC,
+            "SG" => SG,
+            "SE" => SE,
+            "SR" => SR,
+            "OR" => OR,
+            "BL" => BL,
+            "EX" => EX,
+            "CO" => CO,
+            "US" => US,
+            "MS" => MS,
+            "RP" => RP,
+            "BR" => BR,
+            "LG" => LG,
+            "GO" => GO,
+            "GS" => GS,
+            other => return Err(format!("unknown functional constraint `{other}`")),
+        };
+        Ok(fc)
+    }
+}
+
+impl fmt::Display for FunctionalConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+// 逻辑节点: [prefix] class [instance], 例如 ZINV1, MMXU2, Q0XCBR1, LLN0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LogicalNode<'a> {
+    pub prefix: &'a str,
+    pub class: &'a str,
+    pub instance: &'a str,
+}
+
+impl fmt::Display for LogicalNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.prefix, self.class, self.instance)
+    }
+}
+
+// 对象引用: <IED><LDInst>/<LN>$<FC>$<DO>[$<DA>...]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ObjectReference<'a> {
+    pub ied_name: &'a str,
+    pub logical_device: &'a str,
+    pub logical_node: LogicalNode<'a>,
+    pub fc: FunctionalConstraint,
+    pub data_object: &'a str,
+    pub data_attribute: Vec<&'a str>,
+}
+
+impl fmt::Display for ObjectReference<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}/{}${}${}",
+            self.ied_name, self.logical_device, self.logical_node, self.fc, self.data_object
+        )?;
+        for da in &self.data_attribute {
+            write!(f, "${da}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn parse_logical_device<'a>(ied_name: &'a str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    move |input| {
+        let mut parser = tuple((tag(ied_name), take_while1(is_name_char), tag("/")));
+        let (input, (_, ld_inst, _)) = parser(input)?;
+        Ok((input, ld_inst))
+    }
+}
+
+fn parse_logical_node(input: &str) -> IResult<&str, LogicalNode<'_>> {
+    let (input, name) = take_while1(is_name_char)(input)?;
+
+    // LLN0 没有实例号, 其余逻辑节点类名固定 4 个大写字母, 末尾数字为实例号
+    let (rest, instance) = if name.ends_with("LLN0") {
+        (name, "")
+    } else {
+        let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        name.split_at(name.len() - digits)
+    };
+    let (prefix, class) = match rest.len().checked_sub(4).map(|at| rest.split_at(at)) {
+        Some((prefix, class))
+            if class == "LLN0" || class.chars().all(|c| c.is_ascii_uppercase()) =>
+        {
+            (prefix, class)
+        }
+        _ => {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                name,
+                nom::error::ErrorKind::Verify,
+            )));
+        }
+    };
+
+    Ok((
+        input,
+        LogicalNode {
+            prefix,
+            class,
+            instance,
+        },
+    ))
+}
+
+fn parse_fc(input: &str) -> IResult<&str, FunctionalConstraint> {
+    map_res(
+        take_while_m_n(2, 2, |c: char| c.is_ascii_uppercase()),
+        FunctionalConstraint::from_str,
+    )(input)
+}
+
+// 解析对象引用, ied_name 用来从逻辑设备名中切分出 IED 名
+pub fn parse_object_reference<'a>(
+    ied_name: &'a str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, ObjectReference<'a>> {
+    move |input| {
+        let mut parser = tuple((
+            parse_logical_device(ied_name),
+            parse_logical_node,
+            tag("$"),
+            parse_fc,
+            tag("$"),
+            take_while1(is_name_char),
+            many0(preceded(tag("$"), take_while1(is_name_char))),
+            eof,
+        ));
+        let (input, (logical_device, logical_node, _, fc, _, data_object, data_attribute, _)) =
+            parser(input)?;
+        Ok((
+            input,
+            ObjectReference {
+                ied_name,
+                logical_device,
+                logical_node,
+                fc,
+                data_object,
+                data_attribute,
+            },
+        ))
+    }
+}
+
+// 对象引用过滤条件, 为 None 的字段不参与匹配
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceFilter<'f> {
+    pub logical_device: Option<&'f str>,
+    pub ln_class: Option<&'f str>,
+    pub ln_instance: Option<&'f str>,
+    pub fc: Option<FunctionalConstraint>,
+    pub data_object: Option<&'f str>,
+    pub data_attribute: Option<&'f str>,
+}
+
+impl ReferenceFilter<'_> {
+    pub fn matches(&self, reference: &ObjectReference<'_>) -> bool {
+        self.logical_device
+            .is_none_or(|ld| ld == reference.logical_device)
+            && self
+                .ln_class
+                .is_none_or(|class| class == reference.logical_node.class)
+            && self
+                .ln_instance
+                .is_none_or(|inst| inst == reference.logical_node.instance)
+            && self.fc.is_none_or(|fc| fc == reference.fc)
+            && self
+                .data_object
+                .is_none_or(|data_object| data_object == reference.data_object)
+            && self
+                .data_attribute
+                .is_none_or(|da| reference.data_attribute.last() == Some(&da))
+    }
+}
+
+impl Report {
+    // 解析 report/change 中的全部对象引用, 其余上送类型没有引用
+    pub fn references(&self) -> Vec<(Result<ObjectReference<'_>, &str>, &str)> {
+        let DaValues::Change(values) = &self.da_values else {
+            return vec![];
+        };
+
+        values
+            .iter()
+            .map(|(key, value)| {
+                let reference = parse_object_reference(&self.ied_name)(key)
+                    .map(|(_, reference)| reference)
+                    .map_err(|_| key.as_str());
+                (reference, value.as_str())
+            })
+            .collect()
+    }
+
+    // 例如: 逻辑节点类 ZINV 的全部 ST 值
+    pub fn query(&self, filter: &ReferenceFilter<'_>) -> Vec<(ObjectReference<'_>, &str)> {
+        self.references()
+            .into_iter()
+            .filter_map(|(reference, value)| reference.ok().map(|r| (r, value)))
+            .filter(|(reference, _)| filter.matches(reference))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReportKind;
+
+    #[test]
+    fn test_parse_object_reference() {
+        let input = "GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal";
+        let (_, reference) = parse_object_reference("GZYJHYGW001PCS1")(input).unwrap();
+        assert_eq!(
+            reference,
+            ObjectReference {
+                ied_name: "GZYJHYGW001PCS1",
+                logical_device: "Bay01_MC",
+                logical_node: LogicalNode {
+                    prefix: "",
+                    class: "ZINV",
+                    instance: "1",
+                },
+                fc: FunctionalConstraint::ST,
+                data_object: "PwrDrtSt",
+                data_attribute: vec!["stVal"],
+            }
+        );
+        assert_eq!(reference.to_string(), input);
+    }
+
+    #[test]
+    fn test_parse_logical_node() {
+        assert_eq!(
+            parse_logical_node("Q0XCBR1$"),
+            Ok((
+                "$",
+                LogicalNode {
+                    prefix: "Q0",
+                    class: "XCBR",
+                    instance: "1",
+                }
+            ))
+        );
+        assert_eq!(
+            parse_logical_node("LLN0$"),
+            Ok((
+                "$",
+                LogicalNode {
+                    prefix: "",
+                    class: "LLN0",
+                    instance: "",
+                }
+            ))
+        );
+        assert!(parse_logical_node("zinv1$").is_err());
+    }
+
+    #[test]
+    fn test_parse_object_reference_invalid() {
+        let parse = |input| parse_object_reference("GZYJHYGW001PCS1")(input);
+        // IED 名不符
+        assert!(parse("GZYJHYGW001BMS1LD0/MMXU1$MX$TotW$mag$f").is_err());
+        // 未知的功能约束
+        assert!(parse("GZYJHYGW001PCS1LD0/MMXU1$XX$TotW$mag$f").is_err());
+        // 缺少数据对象
+        assert!(parse("GZYJHYGW001PCS1LD0/MMXU1$MX").is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let input = r#"2024-04-06 02:10:07.714  [yjhy/GZYJHYEMS001/report/change]  D:[{"daValues":{"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal":"2","GZYJHYGW001PCS1Bay01_MC/ZINV1$MX$TotW$mag$f":"12.5","GZYJHYGW001PCS1Bay01_MC/MMXU1$ST$Health$stVal":"1"},"dsName":"dsDin","errCode":"0","iedName":"GZYJHYGW001PCS1","ts":"2024-04-06 02:10:07"}]"#;
+        let reports = crate::parse_reports(input).unwrap();
+        assert_eq!(reports[0].da_values.kind(), ReportKind::Change);
+
+        let filter = ReferenceFilter {
+            ln_class: Some("ZINV"),
+            fc: Some(FunctionalConstraint::ST),
+            ..Default::default()
+        };
+        let values = reports[0].query(&filter);
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].0.data_object, "PwrDrtSt");
+        assert_eq!(values[0].1, "2");
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{ErrorCode, Quality, Yjhy};
+
+// 上送类型, 由 topic 的最后几段决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    // 遥信变化上送: yjhy/<gw>/report/change
+    Change,
+    // 遥测变化上送: yjhy/<gw>/report/ai/change
+    AiChange,
+    // 周期上送(总召): yjhy/<gw>/report/period
+    Period,
+}
+
+impl ReportKind {
+    pub fn from_topic(topics: &[&str]) -> Result<Self, ReportError> {
+        match topics {
+            [.., "report", "ai", "change"] => Ok(ReportKind::AiChange),
+            [.., "report", "change"] => Ok(ReportKind::Change),
+            [.., "report", "period"] => Ok(ReportKind::Period),
+            _ => Err(ReportError::UnknownTopic(topics.join("/"))),
+        }
+    }
+}
+
+impl fmt::Display for ReportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportKind::Change => write!(f, "report/change"),
+            ReportKind::AiChange => write!(f, "report/ai/change"),
+            ReportKind::Period => write!(f, "report/period"),
+        }
+    }
+}
+
+// 遥测值三元组: [value, quality, epoch]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalogValue {
+    pub value: f64,
+    pub quality: Quality,
+    // epoch 为 0 表示该点从未刷新过
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl AnalogValue {
+    pub fn is_good(&self) -> bool {
+        self.quality.is_good()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "values", rename_all = "snake_case")]
+pub enum DaValues {
+    // IEC 61850 引用 -> 字符串值
+    Change(BTreeMap<String, String>),
+    AiChange(Vec<AnalogValue>),
+    Period(Vec<AnalogValue>),
+}
+
+impl DaValues {
+    pub fn kind(&self) -> ReportKind {
+        match self {
+            DaValues::Change(_) => ReportKind::Change,
+            DaValues::AiChange(_) => ReportKind::AiChange,
+            DaValues::Period(_) => ReportKind::Period,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            DaValues::Change(values) => values.len(),
+            DaValues::AiChange(values) | DaValues::Period(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 遥测值及其在 daValues 中的下标, report/change 没有遥测值
+    pub fn analog_values(&self) -> impl Iterator<Item = (usize, &AnalogValue)> {
+        let values = match self {
+            DaValues::Change(_) => &[][..],
+            DaValues::AiChange(values) | DaValues::Period(values) => values.as_slice(),
+        };
+        values.iter().enumerate()
+    }
+
+    // 只保留品质有效性为 good 的遥测值, 保留原下标以便对应 inclusion
+    pub fn good_values(&self) -> impl Iterator<Item = (usize, &AnalogValue)> {
+        self.analog_values().filter(|(_, value)| value.is_good())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub ied_name: String,
+    pub ds_name: String,
+    pub err_code: ErrorCode,
+    pub inclusion: Option<String>,
+    pub ts: NaiveDateTime,
+    pub da_values: DaValues,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportError {
+    // 日志行本身无法解析
+    Log(String),
+    // topic 不是已知的上送类型
+    UnknownTopic(String),
+    // daValues 的形状与 topic 不符
+    ShapeMismatch {
+        kind: ReportKind,
+        expected: &'static str,
+        found: &'static str,
+    },
+    // daValues 中某个元素无法解码
+    InvalidValue {
+        kind: ReportKind,
+        at: String,
+        reason: String,
+    },
+    InvalidTs(String),
+    // inclusion 不是十六进制位图
+    InvalidInclusion(String),
+    // inclusion 中置位的个数与遥测值个数不符
+    InclusionMismatch {
+        members: usize,
+        values: usize,
+    },
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::Log(err) => write!(f, "invalid log line: {err}"),
+            ReportError::UnknownTopic(topic) => write!(f, "unknown report topic `{topic}`"),
+            ReportError::ShapeMismatch {
+                kind,
+                expected,
+                found,
+            } => write!(f, "{kind} expects daValues as {expected}, found {found}"),
+            ReportError::InvalidValue { kind, at, reason } => {
+                write!(f, "{kind} daValues[{at}]: {reason}")
+            }
+            ReportError::InvalidTs(ts) => write!(f, "invalid ts `{ts}`"),
+            ReportError::InvalidInclusion(inclusion) => {
+                write!(f, "invalid inclusion `{inclusion}`")
+            }
+            ReportError::InclusionMismatch { members, values } => write!(
+                f,
+                "inclusion selects {members} members but daValues has {values} values"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn decode_change(value: &Value) -> Result<BTreeMap<String, String>, ReportError> {
+    let kind = ReportKind::Change;
+    let Value::Object(map) = value else {
+        return Err(ReportError::ShapeMismatch {
+            kind,
+            expected: "an object of reference to string",
+            found: json_type(value),
+        });
+    };
+
+    map.iter()
+        .map(|(reference, value)| match value {
+            Value::String(s) => Ok((reference.clone(), s.clone())),
+            other => Err(ReportError::InvalidValue {
+                kind,
+                at: reference.clone(),
+                reason: format!("expected a string, found {}", json_type(other)),
+            }),
+        })
+        .collect()
+}
+
+fn decode_triple(
+    kind: ReportKind,
+    index: usize,
+    value: &Value,
+) -> Result<AnalogValue, ReportError> {
+    let invalid = |reason: String| ReportError::InvalidValue {
+        kind,
+        at: index.to_string(),
+        reason,
+    };
+
+    let triple = match value {
+        Value::Array(triple) if triple.len() == 3 => triple,
+        Value::Array(triple) => {
+            return Err(invalid(format!(
+                "expected [value, quality, epoch], found {} elements",
+                triple.len()
+            )));
+        }
+        other => {
+            return Err(invalid(format!(
+                "expected [value, quality, epoch], found {}",
+                json_type(other)
+            )));
+        }
+    };
+
+    let value = triple[0]
+        .as_f64()
+        .ok_or_else(|| invalid(format!("value is {}", json_type(&triple[0]))))?;
+    let quality = triple[1]
+        .as_u64()
+        .and_then(|q| u32::try_from(q).ok())
+        .ok_or_else(|| invalid(format!("quality `{}` is not a u32", triple[1])))?;
+    let epoch = triple[2]
+        .as_i64()
+        .ok_or_else(|| invalid(format!("epoch `{}` is not an integer", triple[2])))?;
+    let timestamp = match epoch {
+        0 => None,
+        secs => Some(
+            DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| invalid(format!("epoch `{secs}` is out of range")))?,
+        ),
+    };
+
+    Ok(AnalogValue {
+        value,
+        quality: Quality::new(quality),
+        timestamp,
+    })
+}
+
+fn decode_triples(kind: ReportKind, value: &Value) -> Result<Vec<AnalogValue>, ReportError> {
+    let Value::Array(triples) = value else {
+        return Err(ReportError::ShapeMismatch {
+            kind,
+            expected: "an array of [value, quality, epoch]",
+            found: json_type(value),
+        });
+    };
+
+    triples
+        .iter()
+        .enumerate()
+        .map(|(index, triple)| decode_triple(kind, index, triple))
+        .collect()
+}
+
+pub fn decode_da_values(kind: ReportKind, value: &Value) -> Result<DaValues, ReportError> {
+    match kind {
+        ReportKind::Change => decode_change(value).map(DaValues::Change),
+        ReportKind::AiChange => decode_triples(kind, value).map(DaValues::AiChange),
+        ReportKind::Period => decode_triples(kind, value).map(DaValues::Period),
+    }
+}
+
+// inclusion 位图从最高位开始, 第 i 位置位表示数据集第 i 个成员被上送
+pub fn inclusion_members(inclusion: &str) -> Result<Vec<usize>, ReportError> {
+    let hex = inclusion
+        .strip_prefix("0x")
+        .or_else(|| inclusion.strip_prefix("0X"))
+        .unwrap_or(inclusion);
+    if hex.is_empty() {
+        return Err(ReportError::InvalidInclusion(inclusion.into()));
+    }
+
+    let mut members = Vec::new();
+    for (at, c) in hex.chars().enumerate() {
+        let nibble = c
+            .to_digit(16)
+            .ok_or_else(|| ReportError::InvalidInclusion(inclusion.into()))?;
+        for bit in 0..4 {
+            if nibble & (0b1000 >> bit) != 0 {
+                members.push(at * 4 + bit);
+            }
+        }
+    }
+    Ok(members)
+}
+
+impl Report {
+    // 遥测值对应的数据集成员下标, 没有 inclusion 时按顺序编号
+    pub fn members(&self) -> Result<Vec<usize>, ReportError> {
+        let values = self.da_values.analog_values().count();
+        let Some(inclusion) = &self.inclusion else {
+            return Ok((0..values).collect());
+        };
+        let members = inclusion_members(inclusion)?;
+        if members.len() != values {
+            return Err(ReportError::InclusionMismatch {
+                members: members.len(),
+                values,
+            });
+        }
+        Ok(members)
+    }
+}
+
+impl Yjhy {
+    pub fn decode(&self, kind: ReportKind) -> Result<Report, ReportError> {
+        let ts = NaiveDateTime::parse_from_str(&self.ts, "%Y-%m-%d %H:%M:%S")
+            .map_err(|_| ReportError::InvalidTs(self.ts.clone()))?;
+
+        Ok(Report {
+            ied_name: self.ied_name.clone(),
+            ds_name: self.ds_name.clone(),
+            err_code: self.err_code.clone(),
+            inclusion: self.inclusion.clone(),
+            ts,
+            da_values: decode_da_values(kind, &self.da_values)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_report_kind_from_topic() {
+        assert_eq!(
+            ReportKind::from_topic(&["yjhy", "GZYJHYEMS001", "report", "change"]),
+            Ok(ReportKind::Change)
+        );
+        assert_eq!(
+            ReportKind::from_topic(&["yjhy", "GZYJHYEMS001", "report", "ai", "change"]),
+            Ok(ReportKind::AiChange)
+        );
+        assert_eq!(
+            ReportKind::from_topic(&["yjhy", "GZYJHYEMS001", "report", "period"]),
+            Ok(ReportKind::Period)
+        );
+        assert_eq!(
+            ReportKind::from_topic(&["byts", "DATA", "CN18b08cf88f1"]),
+            Err(ReportError::UnknownTopic("byts/DATA/CN18b08cf88f1".into()))
+        );
+    }
+
+    #[test]
+    fn test_decode_change() {
+        let value = json!({"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal": "2"});
+        let decoded = decode_da_values(ReportKind::Change, &value).unwrap();
+        let DaValues::Change(map) = decoded else {
+            panic!("expected change values");
+        };
+        assert_eq!(
+            map.get("GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal"),
+            Some(&"2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_triples() {
+        let value = json!([[74187.40000000001, 0, 1712340586], [0, 0, 0]]);
+        let decoded = decode_da_values(ReportKind::AiChange, &value).unwrap();
+        assert_eq!(
+            decoded,
+            DaValues::AiChange(vec![
+                AnalogValue {
+                    value: 74187.40000000001,
+                    quality: Quality::GOOD,
+                    timestamp: DateTime::from_timestamp(1712340586, 0),
+                },
+                AnalogValue {
+                    value: 0.0,
+                    quality: Quality::GOOD,
+                    timestamp: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_good_values() {
+        let value = json!([
+            [1.5, 0, 1712340586],
+            [2.5, 66, 1712340586],
+            [3.5, 1024, 1712340586]
+        ]);
+        let decoded = decode_da_values(ReportKind::Period, &value).unwrap();
+        let good: Vec<(usize, f64)> = decoded
+            .good_values()
+            .map(|(index, value)| (index, value.value))
+            .collect();
+        assert_eq!(good, [(0, 1.5), (2, 3.5)]);
+
+        let (_, invalid) = decoded.analog_values().nth(1).unwrap();
+        assert_eq!(invalid.quality.to_string(), "invalid|failure");
+
+        let value = json!({"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal": "2"});
+        let decoded = decode_da_values(ReportKind::Change, &value).unwrap();
+        assert_eq!(decoded.analog_values().count(), 0);
+    }
+
+    #[test]
+    fn test_inclusion_members() {
+        assert_eq!(inclusion_members("0xFF8"), Ok((0..9).collect()));
+        assert_eq!(inclusion_members("0xFC"), Ok((0..6).collect()));
+        assert_eq!(inclusion_members("0x0000000050"), Ok(vec![33, 35]));
+        assert_eq!(inclusion_members("0x0"), Ok(vec![]));
+        assert_eq!(
+            inclusion_members("0xFG"),
+            Err(ReportError::InvalidInclusion("0xFG".into()))
+        );
+        assert!(inclusion_members("0x").is_err());
+    }
+
+    #[test]
+    fn test_decode_mismatch() {
+        let value = json!({"GZYJHYGW001PCS1Bay01_MC/ZINV1$ST$PwrDrtSt$stVal": "2"});
+        let err = decode_da_values(ReportKind::Period, &value).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "report/period expects daValues as an array of [value, quality, epoch], found an object"
+        );
+
+        let value = json!([[1, 0]]);
+        let err = decode_da_values(ReportKind::AiChange, &value).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "report/ai/change daValues[0]: expected [value, quality, epoch], found 2 elements"
+        );
+    }
+}
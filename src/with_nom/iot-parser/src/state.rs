@@ -0,0 +1,491 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{AnalogValue, DaValues, Report, ReportError, ReportKind};
+
+// 一个数据集: 由 iedName 和 dsName 唯一确定
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct DatasetKey {
+    pub ied_name: String,
+    pub ds_name: String,
+}
+
+impl fmt::Display for DatasetKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.ied_name, self.ds_name)
+    }
+}
+
+// 数据集中的一个点: 遥信按对象引用, 遥测按数据集成员下标
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(untagged)]
+pub enum PointId {
+    Reference(String),
+    Member(usize),
+}
+
+impl fmt::Display for PointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointId::Reference(reference) => write!(f, "{reference}"),
+            PointId::Member(index) => write!(f, "#{index}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum StateValue {
+    Status(String),
+    Analog(AnalogValue),
+}
+
+impl StateValue {
+    // 只比较值和品质, 仅刷新时标不算变化
+    fn same(&self, other: &StateValue) -> bool {
+        match (self, other) {
+            (StateValue::Status(a), StateValue::Status(b)) => a == b,
+            (StateValue::Analog(a), StateValue::Analog(b)) => {
+                a.value == b.value && a.quality == b.quality
+            }
+            _ => false,
+        }
+    }
+}
+
+pub type Snapshot = BTreeMap<PointId, StateValue>;
+
+// 某个点的一次变化
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateEvent {
+    pub ts: NaiveDateTime,
+    pub dataset: DatasetKey,
+    pub point: PointId,
+    pub kind: ReportKind,
+    // 之前没有见过该点时为 None
+    pub old: Option<StateValue>,
+    // 新快照中不再包含该点时为 None
+    pub new: Option<StateValue>,
+}
+
+#[derive(Debug, Clone)]
+struct Update {
+    ts: NaiveDateTime,
+    kind: ReportKind,
+    values: Vec<(PointId, StateValue)>,
+}
+
+impl Update {
+    fn from_report(report: &Report) -> Result<Self, ReportError> {
+        let values = match &report.da_values {
+            DaValues::Change(values) => values
+                .iter()
+                .map(|(reference, value)| {
+                    (
+                        PointId::Reference(reference.clone()),
+                        StateValue::Status(value.clone()),
+                    )
+                })
+                .collect(),
+            DaValues::AiChange(values) | DaValues::Period(values) => report
+                .members()?
+                .into_iter()
+                .zip(values)
+                .map(|(member, value)| (PointId::Member(member), StateValue::Analog(value.clone())))
+                .collect(),
+        };
+        Ok(Update {
+            ts: report.ts,
+            kind: report.da_values.kind(),
+            values,
+        })
+    }
+
+    // period 是全量快照, 会替换之前的状态
+    fn is_snapshot(&self) -> bool {
+        self.kind == ReportKind::Period
+    }
+
+    // 快照只包含按成员下标的点, 只替换这类点, 按引用上送的遥信保留;
+    // 快照中缺少的旧成员在最后以 new = None 通知
+    fn apply(
+        &self,
+        state: &mut Snapshot,
+        mut emit: impl FnMut(&PointId, Option<&StateValue>, Option<&StateValue>),
+    ) {
+        let previous = if self.is_snapshot() {
+            let (members, references) = std::mem::take(state)
+                .into_iter()
+                .partition(|(point, _)| matches!(point, PointId::Member(_)));
+            *state = references;
+            members
+        } else {
+            Snapshot::new()
+        };
+        for (point, value) in &self.values {
+            let old = previous.get(point).or_else(|| state.get(point));
+            if !old.is_some_and(|old| old.same(value)) {
+                emit(point, old, Some(value));
+            }
+            state.insert(point.clone(), value.clone());
+        }
+        for (point, old) in &previous {
+            if !state.contains_key(point) {
+                emit(point, Some(old), None);
+            }
+        }
+    }
+}
+
+// 按 (iedName, dsName) 在最近一次 period 快照上叠加 change, 重建任意时刻的状态
+#[derive(Debug, Default)]
+pub struct StateEngine {
+    timelines: BTreeMap<DatasetKey, Vec<Update>>,
+}
+
+impl StateEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 报告可以乱序到达, 按 ts 插入, 相同 ts 保持到达顺序
+    pub fn apply(&mut self, report: &Report) -> Result<(), ReportError> {
+        let update = Update::from_report(report)?;
+        let key = DatasetKey {
+            ied_name: report.ied_name.clone(),
+            ds_name: report.ds_name.clone(),
+        };
+        let timeline = self.timelines.entry(key).or_default();
+        let at = timeline.partition_point(|u| u.ts <= update.ts);
+        timeline.insert(at, update);
+        Ok(())
+    }
+
+    pub fn datasets(&self) -> impl Iterator<Item = &DatasetKey> {
+        self.timelines.keys()
+    }
+
+    // 时刻 time (含) 之前某个数据集的状态; 快照不包含按引用上送的点, 所以从头重放
+    pub fn dataset_at(&self, key: &DatasetKey, time: NaiveDateTime) -> Snapshot {
+        let mut state = Snapshot::new();
+        let Some(timeline) = self.timelines.get(key) else {
+            return state;
+        };
+        let end = timeline.partition_point(|u| u.ts <= time);
+        for update in &timeline[..end] {
+            update.apply(&mut state, |_, _, _| {});
+        }
+        state
+    }
+
+    // 时刻 time 所有数据集中每个点的值
+    pub fn state_at(&self, time: NaiveDateTime) -> BTreeMap<DatasetKey, Snapshot> {
+        self.timelines
+            .keys()
+            .map(|key| (key.clone(), self.dataset_at(key, time)))
+            .filter(|(_, state)| !state.is_empty())
+            .collect()
+    }
+
+    pub fn value_at(
+        &self,
+        key: &DatasetKey,
+        point: &PointId,
+        time: NaiveDateTime,
+    ) -> Option<StateValue> {
+        self.dataset_at(key, time).remove(point)
+    }
+
+    // 全部变化事件, 按时间排序
+    pub fn events(&self) -> Vec<StateEvent> {
+        let mut events = Vec::new();
+        for (key, timeline) in &self.timelines {
+            let mut state = Snapshot::new();
+            for update in timeline {
+                update.apply(&mut state, |point, old, new| {
+                    events.push(StateEvent {
+                        ts: update.ts,
+                        dataset: key.clone(),
+                        point: point.clone(),
+                        kind: update.kind,
+                        old: old.cloned(),
+                        new: new.cloned(),
+                    })
+                });
+            }
+        }
+        events.sort_by_key(|event| event.ts);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorCode, Quality, decode_da_values};
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 4, 6)
+            .and_then(|date| date.and_hms_opt(hour, minute, 0))
+            .unwrap()
+    }
+
+    fn report(
+        ds_name: &str,
+        kind: ReportKind,
+        ts: NaiveDateTime,
+        inclusion: Option<&str>,
+        values: serde_json::Value,
+    ) -> Report {
+        Report {
+            ied_name: "GZYJHYGW001BMS1".into(),
+            ds_name: ds_name.into(),
+            err_code: ErrorCode::IntErr(0),
+            inclusion: inclusion.map(String::from),
+            ts,
+            da_values: decode_da_values(kind, &values).unwrap(),
+        }
+    }
+
+    fn analog(state: &Snapshot, member: usize) -> Option<f64> {
+        match state.get(&PointId::Member(member)) {
+            Some(StateValue::Analog(value)) => Some(value.value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_state_at() {
+        let mut engine = StateEngine::new();
+        let ain = DatasetKey {
+            ied_name: "GZYJHYGW001BMS1".into(),
+            ds_name: "LD0/dsAin".into(),
+        };
+
+        let period = json!([[1, 0, 1712340586], [2, 0, 1712340586], [3, 0, 1712340586]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 0),
+                Some("0xE"),
+                period,
+            ))
+            .unwrap();
+        // 乱序到达的 change
+        let change = json!([[30, 0, 1712340646]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::AiChange,
+                at(2, 10),
+                Some("0x2"),
+                change,
+            ))
+            .unwrap();
+        let change = json!([[20, 0, 1712340646]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::AiChange,
+                at(2, 5),
+                Some("0x4"),
+                change,
+            ))
+            .unwrap();
+        // 新快照覆盖之前的状态
+        let period = json!([[4, 0, 1712341186], [5, 0, 1712341186]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 15),
+                Some("0xC"),
+                period,
+            ))
+            .unwrap();
+
+        assert!(engine.dataset_at(&ain, at(1, 59)).is_empty());
+        let state = engine.dataset_at(&ain, at(2, 7));
+        assert_eq!(
+            (analog(&state, 0), analog(&state, 1), analog(&state, 2)),
+            (Some(1.0), Some(20.0), Some(3.0))
+        );
+        let state = engine.dataset_at(&ain, at(2, 10));
+        assert_eq!(analog(&state, 2), Some(30.0));
+        let state = engine.dataset_at(&ain, at(3, 0));
+        assert_eq!(
+            (analog(&state, 0), analog(&state, 1), analog(&state, 2)),
+            (Some(4.0), Some(5.0), None)
+        );
+
+        let status = DatasetKey {
+            ied_name: "GZYJHYGW001BMS1".into(),
+            ds_name: "LD0/dsDin".into(),
+        };
+        let reference = "GZYJHYGW001BMS1LD0/GGIO1$ST$Alm1$stVal";
+        engine
+            .apply(&report(
+                "LD0/dsDin",
+                ReportKind::Change,
+                at(2, 3),
+                None,
+                json!({ reference: "1" }),
+            ))
+            .unwrap();
+        assert_eq!(
+            engine.value_at(&status, &PointId::Reference(reference.into()), at(2, 4)),
+            Some(StateValue::Status("1".into()))
+        );
+        assert_eq!(engine.state_at(at(2, 4)).len(), 2);
+        assert_eq!(engine.state_at(at(2, 1)).len(), 1);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut engine = StateEngine::new();
+        let period = json!([[1, 0, 1712340586], [2, 0, 1712340586]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 0),
+                None,
+                period,
+            ))
+            .unwrap();
+        // 值相同, 只刷新了时标, 不产生事件
+        let change = json!([[1, 0, 1712340646], [2, 2, 1712340646]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::AiChange,
+                at(2, 1),
+                None,
+                change,
+            ))
+            .unwrap();
+
+        let events = engine.events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].old, None);
+        let last = &events[2];
+        assert_eq!(last.point, PointId::Member(1));
+        assert_eq!(last.kind, ReportKind::AiChange);
+        let (Some(StateValue::Analog(old)), Some(StateValue::Analog(new))) = (&last.old, &last.new)
+        else {
+            panic!("expected analog values");
+        };
+        assert_eq!((old.quality, new.quality), (Quality::GOOD, Quality::new(2)));
+    }
+
+    #[test]
+    fn test_snapshot_removes_points() {
+        let mut engine = StateEngine::new();
+        let period = json!([[1, 0, 1712340586], [2, 0, 1712340586], [3, 0, 1712340586]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 0),
+                Some("0xE"),
+                period,
+            ))
+            .unwrap();
+        // 新快照不再包含成员 2
+        let period = json!([[1, 0, 1712341186], [2, 0, 1712341186]]);
+        engine
+            .apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 15),
+                Some("0xC"),
+                period,
+            ))
+            .unwrap();
+
+        let events = engine.events();
+        assert_eq!(events.len(), 4);
+        let removed = &events[3];
+        assert_eq!(
+            (removed.ts, &removed.point, removed.kind),
+            (at(2, 15), &PointId::Member(2), ReportKind::Period)
+        );
+        assert!(matches!(
+            (&removed.old, &removed.new),
+            (Some(StateValue::Analog(old)), None) if old.value == 3.0
+        ));
+    }
+
+    // 遥信数据集上也有 period 快照, 快照不影响按引用上送的点
+    #[test]
+    fn test_snapshot_keeps_references() {
+        let mut engine = StateEngine::new();
+        let din = DatasetKey {
+            ied_name: "GZYJHYGW001BMS1".into(),
+            ds_name: "LD0/dsDin".into(),
+        };
+        let reference = PointId::Reference("GZYJHYGW001BMS1LD0/GGIO1$ST$Alm1$stVal".into());
+        let change = |ts, value: &str| {
+            report(
+                "LD0/dsDin",
+                ReportKind::Change,
+                ts,
+                None,
+                json!({ "GZYJHYGW001BMS1LD0/GGIO1$ST$Alm1$stVal": value }),
+            )
+        };
+        engine.apply(&change(at(2, 0), "1")).unwrap();
+        for ts in [at(2, 5), at(2, 15)] {
+            let period = json!([[0, 0, 1712340586], [1, 0, 1712340586]]);
+            engine
+                .apply(&report("LD0/dsDin", ReportKind::Period, ts, None, period))
+                .unwrap();
+        }
+        engine.apply(&change(at(2, 10), "0")).unwrap();
+
+        let events = engine.events();
+        assert!(events.iter().all(|event| event.new.is_some()), "{events:?}");
+        let changes: Vec<_> = events
+            .iter()
+            .filter(|event| event.point == reference)
+            .map(|event| (event.ts, event.new.clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (at(2, 0), Some(StateValue::Status("1".into()))),
+                (at(2, 10), Some(StateValue::Status("0".into()))),
+            ]
+        );
+        // 最后一次快照之后, change 的值仍然叠加在快照上
+        let state = engine.dataset_at(&din, at(3, 0));
+        assert_eq!(state.len(), 3);
+        assert_eq!(state.get(&reference), Some(&StateValue::Status("0".into())));
+        assert_eq!(analog(&state, 1), Some(1.0));
+    }
+
+    #[test]
+    fn test_inclusion_mismatch() {
+        let mut engine = StateEngine::new();
+        let period = json!([[1, 0, 1712340586]]);
+        assert_eq!(
+            engine.apply(&report(
+                "LD0/dsAin",
+                ReportKind::Period,
+                at(2, 0),
+                Some("0x3"),
+                period
+            )),
+            Err(ReportError::InclusionMismatch {
+                members: 2,
+                values: 1
+            })
+        );
+    }
+}
@@ -1,10 +1,15 @@
-use nom::bytes::complete::tag;
-use nom::character::complete::{alphanumeric1, digit1, newline, not_line_ending, space1};
-use nom::multi::{many0, separated_list1};
-use nom::sequence::tuple;
-use nom::{IResult, Needed};
+use iot_log_parser::{JsonDecoder, parse_log_with};
+use nom::IResult;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::io::BufRead;
+
+mod points;
+pub use points::*;
+
+pub use iot_log_parser::{
+    LogEnvelope, LogError, LogRecord, LogRecords, RecordError, decompress, open_log,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,82 +22,26 @@ pub struct Byts {
     pub timestamp: u64,
 }
 
-// 解析服务器时间
-fn parse_server_time(input: &str) -> IResult<&str, &str> {
-    let mut parser = tuple((
-        digit1,
-        tag("-"),
-        digit1,
-        tag("-"),
-        digit1,
-        space1,
-        digit1,
-        tag(":"),
-        digit1,
-        tag(":"),
-        digit1,
-        tag("."),
-        digit1,
-    ));
-    let (input, (year, _, _month, _, _day, _, _hour, _, _minute, _, _seconds, _, _micro_sec)) =
-        parser(input)?;
-    Ok((input, year))
-}
-
-fn parse_topic_name(input: &str) -> IResult<&str, Vec<&str>> {
-    let mut parser = tuple((tag("["), separated_list1(tag("/"), alphanumeric1), tag("]")));
-    let (input, (_, topics, _)) = parser(input)?;
-    Ok((input, topics))
-}
+pub type BytsDecoder = JsonDecoder<Byts>;
 
-fn parse_json_str(input: &str) -> IResult<&str, Byts> {
-    let mut parser = tuple((tag("D:"), not_line_ending));
-    let (input, (_, json)) = parser(input)?;
-    let parsed = serde_json::from_str::<Byts>(json);
-    if let Ok(parsed) = parsed {
-        Ok((input, parsed))
-    } else {
-        Err(nom::Err::Incomplete(Needed::Unknown))
-    }
+pub fn parse_log(input: &str) -> IResult<&str, LogEnvelope<'_, Byts>, LogError<'_>> {
+    parse_log_with(&BytsDecoder::new(), input)
 }
 
-pub fn parse_log(input: &str) -> IResult<&str, Byts> {
-    let mut parser = tuple((
-        parse_server_time,
-        space1,
-        parse_topic_name,
-        space1,
-        parse_json_str,
-        many0(newline),
-    ));
-    let (input, (_ts, _, _topic_name, _, json_str, _)) = parser(input)?;
-    Ok((input, json_str))
+// 逐行读取日志文件, 坏行不会中断读取
+pub fn read_logs<'p, R: BufRead>(reader: R) -> LogRecords<'p, R, Byts> {
+    LogRecords::new(reader, parse_log)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_server_time() {
-        let input = "2024-05-05 00:00:03.294";
-        assert_eq!(parse_server_time(input), Ok(("", "2024")));
-    }
-
-    #[test]
-    fn test_topic_name() {
-        let input = "[byts/DATA/CN18b08cf88f1]";
-        assert_eq!(
-            parse_topic_name(input),
-            Ok(("", vec!["byts", "DATA", "CN18b08cf88f1"]))
-        );
-    }
-
     #[test]
     fn test_json_str() {
         let input = r#"2024-05-05 00:00:03.294  [byts/DATA/CN18b08cf88f1]  D:{"dataType":1,"deviceKey":"CN-18b08cf88f1","deviceSn":"CN-18b08cf88f1","productKey":"8sffV8oVNAd","timestamp":1714838402000}"#;
 
-        if let Ok(res) = parse_json_str(input) {
+        if let Ok(res) = parse_log(input) {
             println!("{:?}", res.1);
         } else {
             println!("{}", input);
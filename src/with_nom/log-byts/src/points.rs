@@ -0,0 +1,520 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, take_while};
+use nom::character::complete::{char, line_ending, not_line_ending, one_of, space0};
+use nom::combinator::{all_consuming, eof, map, opt, value, verify};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::Byts;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointType {
+    Bool,
+    Int,
+    Float,
+    Text,
+}
+
+impl FromStr for PointType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bool" => Ok(PointType::Bool),
+            "int" => Ok(PointType::Int),
+            "float" => Ok(PointType::Float),
+            "text" | "string" => Ok(PointType::Text),
+            other => Err(format!("unknown point type `{other}`")),
+        }
+    }
+}
+
+// 点表中的一个测点
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointDef {
+    pub id: String,
+    pub name: String,
+    pub unit: String,
+    pub scale: f64,
+    pub data_type: PointType,
+    pub group: String,
+}
+
+#[derive(Debug)]
+pub enum PointTableError {
+    Io(std::io::Error),
+    Syntax(String),
+    MissingField {
+        id: String,
+        field: &'static str,
+    },
+    InvalidField {
+        id: String,
+        field: &'static str,
+        value: String,
+    },
+    EmptyName {
+        line: usize,
+        id: String,
+    },
+    DuplicateId {
+        line: usize,
+        id: String,
+    },
+}
+
+impl fmt::Display for PointTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointTableError::Io(err) => write!(f, "{err}"),
+            PointTableError::Syntax(err) => write!(f, "invalid point table: {err}"),
+            PointTableError::MissingField { id, field } => {
+                write!(f, "point `{id}` is missing `{field}`")
+            }
+            PointTableError::InvalidField { id, field, value } => {
+                write!(f, "point `{id}` has invalid {field} `{value}`")
+            }
+            PointTableError::EmptyName { line, id } => {
+                write!(f, "line {line}: point `{id}` has an empty name")
+            }
+            PointTableError::DuplicateId { line, id } => {
+                write!(f, "line {line}: point `{id}` is already defined")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PointTableError {}
+
+// 空行或以 `#` / `;` 开头的注释行
+fn parse_blank(input: &str) -> IResult<&str, ()> {
+    let comment = tuple((one_of("#;"), not_line_ending, alt((line_ending, eof))));
+    value(
+        (),
+        pair(space0, alt((value((), comment), value((), line_ending)))),
+    )(input)
+}
+
+fn parse_csv_field(input: &str) -> IResult<&str, &str> {
+    map(
+        take_while(|c: char| !matches!(c, ',' | '\r' | '\n')),
+        str::trim,
+    )(input)
+}
+
+fn parse_csv_record(input: &str) -> IResult<&str, Vec<&str>> {
+    let record = verify(
+        separated_list1(char(','), parse_csv_field),
+        |record: &[&str]| record != [""],
+    );
+    terminated(record, alt((line_ending, eof)))(input)
+}
+
+// 表头 + 若干行, 逗号分隔, 不支持引号
+fn parse_csv(input: &str) -> IResult<&str, Vec<Vec<&str>>> {
+    all_consuming(terminated(
+        many0(preceded(many0(parse_blank), parse_csv_record)),
+        many0(parse_blank),
+    ))(input)
+}
+
+fn parse_ini_section(input: &str) -> IResult<&str, &str> {
+    terminated(
+        delimited(
+            pair(space0, char('[')),
+            map(is_not("]\r\n"), str::trim),
+            char(']'),
+        ),
+        pair(space0, alt((line_ending, eof))),
+    )(input)
+}
+
+fn parse_ini_property(input: &str) -> IResult<&str, (&str, &str)> {
+    terminated(
+        separated_pair(
+            map(preceded(space0, is_not("=[\r\n#;")), str::trim),
+            char('='),
+            map(not_line_ending, str::trim),
+        ),
+        opt(line_ending),
+    )(input)
+}
+
+type IniSection<'a> = (&'a str, Vec<(&'a str, &'a str)>);
+
+// [id] 段落, 段内为 key = value
+fn parse_ini(input: &str) -> IResult<&str, Vec<IniSection<'_>>> {
+    all_consuming(terminated(
+        many0(preceded(
+            many0(parse_blank),
+            pair(
+                parse_ini_section,
+                many0(preceded(many0(parse_blank), parse_ini_property)),
+            ),
+        )),
+        many0(parse_blank),
+    ))(input)
+}
+
+// part 是 input 的子串, 返回它所在的行号
+fn line_of(input: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - input.as_ptr() as usize;
+    input[..offset].matches('\n').count() + 1
+}
+
+fn build_point(
+    id: &str,
+    line: usize,
+    field: impl Fn(&'static str) -> Option<String>,
+) -> Result<PointDef, PointTableError> {
+    let invalid = |field: &'static str, value: &str| PointTableError::InvalidField {
+        id: id.into(),
+        field,
+        value: value.into(),
+    };
+
+    if id.is_empty() {
+        return Err(PointTableError::MissingField {
+            id: id.into(),
+            field: "id",
+        });
+    }
+    let name = field("name").ok_or_else(|| PointTableError::MissingField {
+        id: id.into(),
+        field: "name",
+    })?;
+    if name.is_empty() {
+        return Err(PointTableError::EmptyName {
+            line,
+            id: id.into(),
+        });
+    }
+    let scale = match field("scale").filter(|scale| !scale.is_empty()) {
+        Some(scale) => scale.parse::<f64>().map_err(|_| invalid("scale", &scale))?,
+        None => 1.0,
+    };
+    let data_type = match field("type").filter(|data_type| !data_type.is_empty()) {
+        Some(data_type) => data_type.parse().map_err(|_| invalid("type", &data_type))?,
+        None => PointType::Float,
+    };
+
+    Ok(PointDef {
+        id: id.into(),
+        name,
+        unit: field("unit").unwrap_or_default(),
+        scale,
+        data_type,
+        group: field("group").unwrap_or_default(),
+    })
+}
+
+// 同一个 ID 只能定义一次
+fn insert(
+    points: &mut HashMap<String, PointDef>,
+    point: PointDef,
+    line: usize,
+) -> Result<(), PointTableError> {
+    if points.contains_key(&point.id) {
+        return Err(PointTableError::DuplicateId { line, id: point.id });
+    }
+    points.insert(point.id.clone(), point);
+    Ok(())
+}
+
+// 测点字典, 把 Byts.data 中的数字 ID 映射为名称、单位、系数、类型和分组
+#[derive(Debug, Clone, Default)]
+pub struct PointTable {
+    points: HashMap<String, PointDef>,
+}
+
+impl PointTable {
+    // 表头至少包含 id 和 name, 其余列 unit/scale/type/group 可选
+    pub fn from_csv(input: &str) -> Result<Self, PointTableError> {
+        let (_, records) = parse_csv(input).map_err(|e| PointTableError::Syntax(e.to_string()))?;
+        let mut records = records.into_iter();
+        let header = records
+            .next()
+            .ok_or_else(|| PointTableError::Syntax("missing header".into()))?;
+        let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+        let id_column =
+            column("id").ok_or_else(|| PointTableError::Syntax("missing `id` column".into()))?;
+
+        let mut points = HashMap::new();
+        for record in records {
+            let id = record.get(id_column).copied().unwrap_or_default();
+            let line = line_of(input, record[0]);
+            let point = build_point(id, line, |field| {
+                column(field)
+                    .and_then(|at| record.get(at))
+                    .map(|value| value.to_string())
+            })?;
+            insert(&mut points, point, line)?;
+        }
+        Ok(PointTable { points })
+    }
+
+    // 每个测点一个 [id] 段落
+    pub fn from_ini(input: &str) -> Result<Self, PointTableError> {
+        let (_, sections) = parse_ini(input).map_err(|e| PointTableError::Syntax(e.to_string()))?;
+
+        let mut points = HashMap::new();
+        for (id, properties) in sections {
+            let line = line_of(input, id);
+            let point = build_point(id, line, |field| {
+                properties
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(field))
+                    .map(|(_, value)| value.to_string())
+            })?;
+            insert(&mut points, point, line)?;
+        }
+        Ok(PointTable { points })
+    }
+
+    // 根据扩展名选择 CSV 或 INI
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PointTableError> {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path).map_err(PointTableError::Io)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ini") => Self::from_ini(&input),
+            _ => Self::from_csv(&input),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PointDef> {
+        self.points.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn decode<'t>(&'t self, byts: &Byts) -> DecodedData<'t> {
+        let mut decoded = DecodedData::default();
+        let Value::Object(data) = &byts.data else {
+            return decoded;
+        };
+
+        for (key, raw) in data {
+            let Some(point) = self.points.get(key) else {
+                decoded.unknown.insert(key.clone(), raw.clone());
+                continue;
+            };
+            match decode_value(point, raw) {
+                Some(value) => decoded.values.push(PointValue { point, value }),
+                None => decoded.invalid.push(InvalidPoint {
+                    point,
+                    raw: raw.clone(),
+                }),
+            }
+        }
+        decoded
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum TypedValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+fn decode_value(point: &PointDef, raw: &Value) -> Option<TypedValue> {
+    let text = match raw {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+
+    let value = match point.data_type {
+        PointType::Text => TypedValue::Text(text),
+        PointType::Bool => match text.as_str() {
+            "1" | "true" => TypedValue::Bool(true),
+            "0" | "false" => TypedValue::Bool(false),
+            _ => return None,
+        },
+        PointType::Int if point.scale == 1.0 => TypedValue::Int(text.parse().ok()?),
+        PointType::Int | PointType::Float => {
+            TypedValue::Float(text.parse::<f64>().ok()? * point.scale)
+        }
+    };
+    Some(value)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointValue<'t> {
+    pub point: &'t PointDef,
+    pub value: TypedValue,
+}
+
+// 点表中有定义, 但值与类型不符
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidPoint<'t> {
+    pub point: &'t PointDef,
+    pub raw: Value,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DecodedData<'t> {
+    pub values: Vec<PointValue<'t>>,
+    // 点表中没有定义的键, 通常意味着固件新增了测点
+    pub unknown: BTreeMap<String, Value>,
+    pub invalid: Vec<InvalidPoint<'t>>,
+}
+
+impl DecodedData<'_> {
+    pub fn by_group(&self, group: &str) -> impl Iterator<Item = &PointValue<'_>> {
+        self.values.iter().filter(move |v| v.point.group == group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "# BMS 点表\n\
+                       id,name,unit,scale,type,group\n\
+                       3880,soc,%,1,int,battery\n\
+                       3890,cell_voltage,V,0.001,float,battery\n\
+                       3900,running,,,bool,status\r\n\
+                       \n\
+                       3910,fw_version,,,text,status\n";
+
+    const INI: &str = "; BMS 点表\n\
+                       [3880]\n\
+                       name = soc\n\
+                       unit = %\n\
+                       type = int\n\
+                       group = battery\n\
+                       \n\
+                       [3890]\n\
+                       name = cell_voltage\n\
+                       unit = V\n\
+                       scale = 0.001\n\
+                       group = battery\n";
+
+    fn sample(data: Value) -> Byts {
+        Byts {
+            data,
+            data_type: 1,
+            device_key: "CN-18b08cf88f1".into(),
+            device_sn: "CN-18b08cf88f1".into(),
+            product_key: "8sffV8oVNAd".into(),
+            timestamp: 1714838402000,
+        }
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let table = PointTable::from_csv(CSV).unwrap();
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            table.get("3890"),
+            Some(&PointDef {
+                id: "3890".into(),
+                name: "cell_voltage".into(),
+                unit: "V".into(),
+                scale: 0.001,
+                data_type: PointType::Float,
+                group: "battery".into(),
+            })
+        );
+        assert_eq!(table.get("3900").unwrap().scale, 1.0);
+
+        assert!(PointTable::from_csv("name,unit\nsoc,%").is_err());
+        assert!(matches!(
+            PointTable::from_csv("id,name,scale\n3880,soc,x"),
+            Err(PointTableError::InvalidField { field: "scale", .. })
+        ));
+        assert!(matches!(
+            PointTable::from_csv("id,name,type\n3880,soc,complex"),
+            Err(PointTableError::InvalidField { field: "type", .. })
+        ));
+        assert!(matches!(
+            PointTable::from_csv("id,name\n3880,soc\n\n3880,soh"),
+            Err(PointTableError::DuplicateId { line: 4, .. })
+        ));
+        assert!(matches!(
+            PointTable::from_csv("id,name,unit\n3880,soc,%\n3890, ,V"),
+            Err(PointTableError::EmptyName { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_ini() {
+        let table = PointTable::from_ini(INI).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get("3880").unwrap().data_type, PointType::Int);
+        assert_eq!(table.get("3890").unwrap().data_type, PointType::Float);
+        assert_eq!(table.get("3890").unwrap().unit, "V");
+
+        assert!(matches!(
+            PointTable::from_ini("[3880]\nunit = %\n"),
+            Err(PointTableError::MissingField { field: "name", .. })
+        ));
+        assert!(PointTable::from_ini("name = soc\n").is_err());
+        assert!(matches!(
+            PointTable::from_ini("[3880]\nname = soc\n; 重复\n[3880]\nname = soh\n"),
+            Err(PointTableError::DuplicateId { line: 4, .. })
+        ));
+        assert!(matches!(
+            PointTable::from_ini("[3880]\nname =\n"),
+            Err(PointTableError::EmptyName { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode() {
+        let table = PointTable::from_csv(CSV).unwrap();
+        let byts = sample(serde_json::json!({
+            "3880": "50",
+            "3890": "3700",
+            "3900": "1",
+            "3910": "v1.2",
+            "QA-gjtsgzz": "0",
+            "logTime": "2024-05-05 00:00:03",
+        }));
+        let decoded = table.decode(&byts);
+
+        let values: Vec<_> = decoded
+            .values
+            .iter()
+            .map(|v| (v.point.name.as_str(), v.value.clone()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("soc", TypedValue::Int(50)),
+                ("cell_voltage", TypedValue::Float(3.7)),
+                ("running", TypedValue::Bool(true)),
+                ("fw_version", TypedValue::Text("v1.2".into())),
+            ]
+        );
+        assert_eq!(
+            decoded.unknown.keys().collect::<Vec<_>>(),
+            ["QA-gjtsgzz", "logTime"]
+        );
+        assert!(decoded.invalid.is_empty());
+        assert_eq!(decoded.by_group("battery").count(), 2);
+
+        let decoded = table.decode(&sample(serde_json::json!({ "3880": "full" })));
+        assert!(decoded.values.is_empty());
+        assert_eq!(decoded.invalid[0].point.name, "soc");
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+
+use chrono::NaiveDateTime;
+
+use crate::{Zjkg, compare_tags};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PivotError {
+    // Image.t 不是 `%Y-%m-%d %H:%M:%S%.3f` 格式
+    InvalidTime { mid: String, t: String },
+}
+
+impl fmt::Display for PivotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PivotError::InvalidTime { mid, t } => {
+                write!(f, "invalid image time `{t}` in message `{mid}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PivotError {}
+
+// 以时间为行、(mid, tag) 为列的宽表, 多条消息的同一时刻合并为一行.
+// 不带簇编号的 tag (如 `BMS_pack_IoStatus`) 在不同 mid 中同名, 所以列要带上 mid
+#[derive(Debug, Default)]
+pub struct WideTable {
+    columns: Vec<(String, String)>,
+    index: HashMap<(String, String), usize>,
+    rows: BTreeMap<NaiveDateTime, HashMap<usize, f64>>,
+}
+
+impl WideTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 依次 push 每条消息, 遇到第一个错误即返回
+    pub fn from_messages<'z>(
+        messages: impl IntoIterator<Item = &'z Zjkg>,
+    ) -> Result<Self, PivotError> {
+        let mut table = WideTable::new();
+        for zjkg in messages {
+            table.push(zjkg)?;
+        }
+        Ok(table)
+    }
+
+    fn column(&mut self, mid: &str, tag: &str) -> usize {
+        let key = (mid.to_string(), tag.to_string());
+        if let Some(&at) = self.index.get(&key) {
+            return at;
+        }
+        self.columns.push(key.clone());
+        self.index.insert(key, self.columns.len() - 1);
+        self.columns.len() - 1
+    }
+
+    // 非数值的 tag 会被忽略, 同一时刻同一 mid 的同一 tag 以后到的值为准;
+    // 先检查所有 image 的时间, 出错时表格不变
+    pub fn push(&mut self, zjkg: &Zjkg) -> Result<(), PivotError> {
+        let times = zjkg
+            .images
+            .iter()
+            .map(|image| {
+                image.time().ok_or_else(|| PivotError::InvalidTime {
+                    mid: zjkg.mid.clone(),
+                    t: image.t.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (image, time) in zjkg.images.iter().zip(times) {
+            let Some(tags) = image.tags.as_object() else {
+                continue;
+            };
+            for (name, value) in tags {
+                let value = match value {
+                    serde_json::Value::Bool(b) => f64::from(u8::from(*b)),
+                    value => match value.as_f64() {
+                        Some(value) => value,
+                        None => continue,
+                    },
+                };
+                let column = self.column(&zjkg.mid, name);
+                self.rows.entry(time).or_default().insert(column, value);
+            }
+        }
+        Ok(())
+    }
+
+    // 按 TagPath 排序的 (mid, tag), tag 相同时按 mid 排序
+    pub fn columns(&self) -> Vec<(&str, &str)> {
+        let mut columns: Vec<(&str, &str)> = self
+            .columns
+            .iter()
+            .map(|(mid, tag)| (mid.as_str(), tag.as_str()))
+            .collect();
+        columns.sort_by(|a, b| compare_tags(a.1, b.1).then_with(|| a.0.cmp(b.0)));
+        columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // 按时间升序输出每一行, 缺失的值为 None
+    pub fn rows(&self) -> impl Iterator<Item = (NaiveDateTime, Vec<Option<f64>>)> + '_ {
+        let order: Vec<usize> = self
+            .columns()
+            .iter()
+            .map(|&(mid, tag)| self.index[&(mid.to_string(), tag.to_string())])
+            .collect();
+        self.rows.iter().map(move |(time, values)| {
+            let values = order.iter().map(|at| values.get(at).copied()).collect();
+            (*time, values)
+        })
+    }
+
+    // 列名为 `<mid>/<tag>`
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "time")?;
+        for (mid, tag) in self.columns() {
+            write!(w, ",{}", csv_field(&format!("{mid}/{tag}")))?;
+        }
+        writeln!(w)?;
+
+        for (time, values) in self.rows() {
+            write!(w, "{}", time.format("%Y-%m-%d %H:%M:%S%.3f"))?;
+            for value in values {
+                match value {
+                    Some(value) => write!(w, ",{value}")?,
+                    None => write!(w, ",")?,
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+// CSV 字段中含有逗号、引号或换行时加引号
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zjkg(json: &str) -> Zjkg {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_pivot() {
+        let messages = [
+            zjkg(
+                r#"{"ver":211,"mid":"pack2","nm":"pack2","images":[
+                    {"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_2_ele_u":672.4,"BMS_cell_2_u_10":3.3,"BMS_cell_2_u_2":3.31}},
+                    {"t":"2024-05-05 00:00:20.009","tags":{"BMS_pack_2_ele_u":672.5,"BMS_pack_IoStatus":true}}]}"#,
+            ),
+            zjkg(
+                r#"{"ver":211,"mid":"pack3","nm":"pack3","images":[
+                    {"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_3_ele_u":671.0,"BMS_pack_3_ele_mode":"idle","BMS_pack_IoStatus":false}}]}"#,
+            ),
+        ];
+        let table = WideTable::from_messages(&messages).unwrap();
+
+        assert_eq!(
+            table.columns(),
+            [
+                ("pack2", "BMS_cell_2_u_2"),
+                ("pack2", "BMS_cell_2_u_10"),
+                ("pack2", "BMS_pack_IoStatus"),
+                ("pack3", "BMS_pack_IoStatus"),
+                ("pack2", "BMS_pack_2_ele_u"),
+                ("pack3", "BMS_pack_3_ele_u"),
+            ]
+        );
+        assert_eq!(table.len(), 2);
+
+        let mut out = Vec::new();
+        table.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,pack2/BMS_cell_2_u_2,pack2/BMS_cell_2_u_10,pack2/BMS_pack_IoStatus,\
+             pack3/BMS_pack_IoStatus,pack2/BMS_pack_2_ele_u,pack3/BMS_pack_3_ele_u\n\
+             2024-05-05 00:00:19.009,3.31,3.3,,0,672.4,671\n\
+             2024-05-05 00:00:20.009,,,1,,672.5,\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_header() {
+        let message = zjkg(
+            r#"{"ver":211,"mid":"pack,2","nm":"pack2","images":[
+                {"t":"2024-05-05 00:00:19.009","tags":{"BMS_\"u\"":1}}]}"#,
+        );
+        let mut out = Vec::new();
+        WideTable::from_messages([&message])
+            .unwrap()
+            .write_csv(&mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,\"pack,2/BMS_\"\"u\"\"\"\n2024-05-05 00:00:19.009,1\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        let message = zjkg(
+            r#"{"ver":211,"mid":"pack2","nm":"pack2","images":[
+                {"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_2_ele_u":672.4}},
+                {"t":"2024-05-05","tags":{}}]}"#,
+        );
+        let mut table = WideTable::new();
+        assert_eq!(
+            table.push(&message),
+            Err(PivotError::InvalidTime {
+                mid: "pack2".into(),
+                t: "2024-05-05".into(),
+            })
+        );
+        // 出错的消息不会留下一半的数据
+        assert!(table.is_empty());
+        assert!(table.columns().is_empty());
+        assert!(WideTable::from_messages([&message]).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use nom::IResult;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{alpha1, alphanumeric1, u32};
+use nom::combinator::{all_consuming, opt, peek};
+use nom::sequence::{preceded, terminated, tuple};
+
+// 结构化的 tag 名, 例如 `BMS_pack_2_ele_MaxDisChgPwr`:
+// subsystem = BMS, component = pack, pack = 2, category = ele, signal = MaxDisChgPwr
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagPath<'a> {
+    pub subsystem: &'a str,
+    pub component: &'a str,
+    // 电池簇编号, 汇总类 tag (如 `BMS_pack_IoStatus`) 没有
+    pub pack: Option<u32>,
+    pub category: Option<&'a str>,
+    pub signal: &'a str,
+}
+
+fn parse_segment(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c != '_')(input)
+}
+
+// `<subsystem>_<component>[_<pack>[_<category>]]_<signal>`
+pub fn parse_tag(input: &str) -> IResult<&str, TagPath<'_>> {
+    let (input, (subsystem, _, component, _)) =
+        tuple((alphanumeric1, tag("_"), alpha1, tag("_")))(input)?;
+    let (input, pack) = opt(terminated(u32, tag("_")))(input)?;
+    // 有编号时, 若第一段后面跟着以字母开头的段, 第一段为分类, 其余为信号名.
+    // 纯数字的段只作为信号名的序号, 所以 `u_17` 和 `input_8` 一样是信号名
+    let (input, category) = match pack {
+        Some(_) => opt(terminated(parse_segment, preceded(tag("_"), peek(alpha1))))(input)?,
+        None => (input, None),
+    };
+    let (input, signal) = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_')(input)?;
+    Ok((
+        input,
+        TagPath {
+            subsystem,
+            component,
+            pack,
+            category,
+            signal,
+        },
+    ))
+}
+
+impl<'a> TagPath<'a> {
+    pub fn parse(name: &'a str) -> Option<Self> {
+        all_consuming(parse_tag)(name).ok().map(|(_, path)| path)
+    }
+
+    // 信号名末尾的数字按数值排序, 使 `u_2` 排在 `u_10` 之前
+    fn signal_key(&self) -> (&str, Option<u64>) {
+        let digits = self.signal.len()
+            - self
+                .signal
+                .trim_end_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let (prefix, number) = self.signal.split_at(self.signal.len() - digits);
+        (prefix, number.parse().ok())
+    }
+}
+
+impl Ord for TagPath<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.subsystem, self.component, self.pack, self.category)
+            .cmp(&(other.subsystem, other.component, other.pack, other.category))
+            .then_with(|| self.signal_key().cmp(&other.signal_key()))
+    }
+}
+
+impl PartialOrd for TagPath<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for TagPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.subsystem, self.component)?;
+        if let Some(pack) = self.pack {
+            write!(f, "_{pack}")?;
+        }
+        if let Some(category) = self.category {
+            write!(f, "_{category}")?;
+        }
+        write!(f, "_{}", self.signal)
+    }
+}
+
+// 按 TagPath 排序, 无法解析的 tag 名排在最后
+pub fn compare_tags(a: &str, b: &str) -> Ordering {
+    match (TagPath::parse(a), TagPath::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_path() {
+        let path = TagPath::parse("BMS_pack_2_ele_MaxDisChgPwr").unwrap();
+        assert_eq!(
+            path,
+            TagPath {
+                subsystem: "BMS",
+                component: "pack",
+                pack: Some(2),
+                category: Some("ele"),
+                signal: "MaxDisChgPwr",
+            }
+        );
+
+        let path = TagPath::parse("BMS_pack_2_sts_input_8").unwrap();
+        assert_eq!((path.category, path.signal), (Some("sts"), "input_8"));
+
+        let path = TagPath::parse("BMS_cell_2_u_17").unwrap();
+        assert_eq!(
+            (path.pack, path.category, path.signal),
+            (Some(2), None, "u_17")
+        );
+
+        let path = TagPath::parse("BMS_cell_2_temp_u_17").unwrap();
+        assert_eq!(
+            (path.pack, path.category, path.signal),
+            (Some(2), Some("temp"), "u_17")
+        );
+
+        let path = TagPath::parse("BMS_pack_IoStatus").unwrap();
+        assert_eq!(
+            (path.pack, path.category, path.signal),
+            (None, None, "IoStatus")
+        );
+
+        for name in [
+            "BMS_pack_2_ele_MaxDisChgPwr",
+            "BMS_cell_2_temp_102",
+            "BMS_cell_2_u_17",
+            "BMS_pack_2_sts_input_8",
+            "BMS_cell_IoStatus",
+        ] {
+            assert_eq!(TagPath::parse(name).unwrap().to_string(), name);
+        }
+
+        assert_eq!(TagPath::parse("BMS"), None);
+        assert_eq!(TagPath::parse("BMS_pack_"), None);
+    }
+
+    #[test]
+    fn test_compare_tags() {
+        let mut names = vec![
+            "voltage",
+            "BMS_cell_2_u_10",
+            "BMS_pack_2_ele_u",
+            "BMS_cell_2_u_2",
+            "BMS_cell_IoStatus",
+        ];
+        names.sort_by(|a, b| compare_tags(a, b));
+        assert_eq!(
+            names,
+            [
+                "BMS_cell_IoStatus",
+                "BMS_cell_2_u_2",
+                "BMS_cell_2_u_10",
+                "BMS_pack_2_ele_u",
+                "voltage",
+            ]
+        );
+    }
+}
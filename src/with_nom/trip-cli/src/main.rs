@@ -1,23 +1,39 @@
-use trip_lang::parse_trips;
+use std::path::PathBuf;
 
-fn main() {
-    let input = r#"
-Russia
-    Vladivostok : 43.131621,131.923828 : 4
-    Ulan Ude : 51.841624,107.608101 : 2
-    Saint Petersburg : 59.939977,30.315785 : 10
-Norway
-    Oslo : 59.914289,10.738739 : 2
-    Bergen : 60.388533,5.331856 : 4
-Ukraine
-    Kiev : 50.456001,30.50384 : 3
-Switzerland
-    Wengen : 46.608265,7.922065 : 3
-    Bern : 46.949076,7.448151 : 1"#;
+use clap::Parser;
+use nom::Err;
+use nom::error::Error;
+use trip_lang::{Itinerary, parse_trips};
+use trip_model::cli::{self, Command};
+
+#[derive(Parser, Debug)]
+#[command(name = "trip-cli")]
+#[command(version, about = "Parse trip itineraries with nom")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    if let Ok((_, trips)) = parse_trips(input) {
-        for trip in trips {
-            println!("{:?}", trip);
+fn load(file: Option<&PathBuf>) -> Result<Itinerary, String> {
+    let input = cli::read(file)?;
+    match parse_trips(&input) {
+        Ok(("", itinerary)) => Ok(itinerary),
+        // 无法解析的部分从哪一行开始
+        Ok((rest, _))
+        | Err(Err::Error(Error { input: rest, .. }) | Err::Failure(Error { input: rest, .. })) => {
+            Err(format!(
+                "Error parsing itinerary: unexpected input at line {}",
+                input[..input.len() - rest.len()].matches('\n').count() + 1
+            ))
         }
+        Err(Err::Incomplete(_)) => Err("Error parsing itinerary: unexpected end of input".into()),
+    }
+}
+
+fn main() {
+    let command = Cli::parse().command.unwrap_or(Command::Show { file: None });
+    if let Err(e) = cli::run(command, load) {
+        eprintln!("{e}");
+        std::process::exit(1);
     }
 }
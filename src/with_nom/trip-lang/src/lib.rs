@@ -1,83 +1,90 @@
 use nom::IResult;
-use nom::bytes::complete::{tag, take_until};
-use nom::character::complete::{alpha1, newline, space0, space1, u16};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{line_ending, space0, space1, u32};
+use nom::combinator::{eof, map_res};
 use nom::multi::{many0, many1};
 use nom::number::complete::double;
 use nom::sequence::{separated_pair, tuple};
 
-#[derive(Debug, PartialEq)]
-struct Coordinate {
-    lat: f64,
-    lon: f64,
-}
-
-#[derive(Debug, PartialEq)]
-struct Destination {
-    name: String,
-    coord: Coordinate,
-    sales: u16,
-}
+pub use trip_model::*;
 
-#[derive(Debug, PartialEq)]
-pub struct Trip {
-    country: String,
-    destination: Vec<Destination>,
+// 空行和只有空白的行
+fn parse_blank_lines(input: &str) -> IResult<&str, ()> {
+    let (input, _) = many0(tuple((space0, line_ending)))(input)?;
+    Ok((input, ()))
 }
 
+// 国家名独占一行, 可以缩进, 不能包含 `:`
 fn parse_country(input: &str) -> IResult<&str, &str> {
-    let mut parser = tuple((many0(newline), space0, alpha1, newline));
+    let mut parser = tuple((
+        parse_blank_lines,
+        space0,
+        take_till1(|c| c == ':' || c == '\n' || c == '\r'),
+        line_ending,
+    ));
     let (input, (_, _, name, _)) = parser(input)?;
-    Ok((input, name))
+    Ok((input, name.trim()))
 }
 
+// 目的地名不能跨行, 也不能包含 `:`
 fn parse_destination_name(input: &str) -> IResult<&str, &str> {
-    let (input, name) = take_until(" : ")(input)?;
-    Ok((input, name))
+    let (input, name) = take_till1(|c| c == ':' || c == '\n' || c == '\r')(input)?;
+    Ok((input, name.trim()))
+}
+
+// `:` 两侧的空白可有可无, 与 trip-parser 相同
+fn parse_separator(input: &str) -> IResult<&str, ()> {
+    let (input, _) = tuple((space0, tag(":"), space0))(input)?;
+    Ok((input, ()))
 }
 
 fn parse_lat_long(input: &str) -> IResult<&str, Coordinate> {
-    let mut parser = separated_pair(double, tag(","), double);
-    let (input, (lat, lon)) = parser(input)?;
-    Ok((input, Coordinate { lat, lon }))
+    let mut parser = map_res(separated_pair(double, tag(","), double), |(lat, lon)| {
+        Coordinate::new(lat, lon)
+    });
+    parser(input)
 }
 
 fn parse_destination(input: &str) -> IResult<&str, Destination> {
     let mut parser = tuple((
         space1,
         parse_destination_name,
-        tag(" : "),
+        parse_separator,
         parse_lat_long,
-        tag(" : "),
-        u16,
-        many0(newline),
+        parse_separator,
+        u32,
+        space0,
+        alt((line_ending, eof)),
     ));
-    let (input, (_, name, _, coord, _, sales, _)) = parser(input)?;
+    let (input, (_, name, _, coordinate, _, tickets, _, _)) = parser(input)?;
     Ok((
         input,
         Destination {
             name: name.into(),
-            coord,
-            sales,
+            coordinate,
+            tickets,
         },
     ))
 }
 
-fn parse_trip(input: &str) -> IResult<&str, Trip> {
+fn parse_trip(input: &str) -> IResult<&str, Country> {
     let mut parser = tuple((parse_country, many1(parse_destination)));
-    let (input, (country, destination)) = parser(input)?;
+    let (input, (name, destinations)) = parser(input)?;
     Ok((
         input,
-        Trip {
-            country: country.into(),
-            destination: destination.into_iter().map(|d| d).collect(),
+        Country {
+            name: name.into(),
+            destinations,
         },
     ))
 }
 
-pub fn parse_trips(input: &str) -> IResult<&str, Vec<Trip>> {
-    let mut parser = many1(parse_trip);
-    let (input, trips) = parser(input)?;
-    Ok((input, trips))
+// 末尾的空行一并消耗; 没有国家时为空行程, 与 trip-parser 相同
+pub fn parse_trips(input: &str) -> IResult<&str, Itinerary> {
+    let mut parser = tuple((many0(parse_trip), parse_blank_lines, space0));
+    let (input, (countries, _, _)) = parser(input)?;
+    Ok((input, Itinerary { countries }))
 }
 
 #[cfg(test)]
@@ -93,11 +100,11 @@ mod tests {
                 "",
                 Destination {
                     name: "Oslo".into(),
-                    coord: Coordinate {
+                    coordinate: Coordinate {
                         lat: 59.914289,
                         lon: 10.738739
                     },
-                    sales: 2
+                    tickets: 2
                 }
             ))
         );
@@ -109,11 +116,11 @@ mod tests {
                 "",
                 Destination {
                     name: "Ulan Ude".into(),
-                    coord: Coordinate {
+                    coordinate: Coordinate {
                         lat: 51.841624,
                         lon: 107.608101
                     },
-                    sales: 2
+                    tickets: 2
                 }
             ))
         );
@@ -124,13 +131,13 @@ mod tests {
         let input = "Oslo : 59.914289,10.738739 : 2";
         assert_eq!(
             parse_destination_name(input),
-            Ok((" : 59.914289,10.738739 : 2", "Oslo".into()))
+            Ok((": 59.914289,10.738739 : 2", "Oslo"))
         );
 
         let input = "Ulan Ude : 51.841624,107.608101 : 2";
         assert_eq!(
             parse_destination_name(input),
-            Ok((" : 51.841624,107.608101 : 2", "Ulan Ude").into())
+            Ok((": 51.841624,107.608101 : 2", "Ulan Ude"))
         );
     }
 
@@ -162,32 +169,32 @@ mod tests {
             parse_trip(input),
             Ok((
                 "",
-                Trip {
-                    country: "Russia".into(),
-                    destination: vec![
+                Country {
+                    name: "Russia".into(),
+                    destinations: vec![
                         Destination {
                             name: "Vladivostok".into(),
-                            coord: Coordinate {
+                            coordinate: Coordinate {
                                 lat: 43.131621,
                                 lon: 131.923828
                             },
-                            sales: 4
+                            tickets: 4
                         },
                         Destination {
                             name: "Ulan Ude".into(),
-                            coord: Coordinate {
+                            coordinate: Coordinate {
                                 lat: 51.841624,
                                 lon: 107.608101
                             },
-                            sales: 2
+                            tickets: 2
                         },
                         Destination {
                             name: "Saint Petersburg".into(),
-                            coord: Coordinate {
+                            coordinate: Coordinate {
                                 lat: 59.939977,
                                 lon: 30.315785
                             },
-                            sales: 10
+                            tickets: 10
                         },
                     ]
                 }
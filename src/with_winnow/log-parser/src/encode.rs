@@ -0,0 +1,397 @@
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::{
+    Apdu, Asdu, Cot, Cp56Time2a, Direction, Element, Iec104Error, InformationObject, IotLog, START,
+    TypeId, UFunction,
+};
+
+// APDU 长度字节最大为 253 (控制域 4 字节 + ASDU)
+const MAX_APDU_LENGTH: usize = 253;
+const MAX_SEQ: u16 = 0x7fff;
+
+impl Cot {
+    pub fn new(cause: u8) -> Self {
+        Cot {
+            cause,
+            negative: false,
+            test: false,
+            originator: 0,
+        }
+    }
+
+    fn encode(&self) -> [u8; 2] {
+        let mut cause = self.cause & 0x3f;
+        if self.negative {
+            cause |= 0x40;
+        }
+        if self.test {
+            cause |= 0x80;
+        }
+        [cause, self.originator]
+    }
+}
+
+impl Cp56Time2a {
+    // 年份只有两位 (按 2000 年起算), 2000..=2099 之外的时间返回错误
+    pub fn from_naive(time: NaiveDateTime) -> Result<Self, Iec104Error> {
+        if !(2000..=2099).contains(&time.year()) {
+            return Err(Iec104Error::TimeOutOfRange(time));
+        }
+        Ok(Cp56Time2a {
+            millis: (time.second() * 1000 + time.nanosecond() / 1_000_000) as u16,
+            minute: time.minute() as u8,
+            invalid: false,
+            hour: time.hour() as u8,
+            summer_time: false,
+            day: time.day() as u8,
+            weekday: time.weekday().number_from_monday() as u8,
+            month: time.month() as u8,
+            year: (time.year() - 2000) as u8,
+        })
+    }
+
+    // 字段越界 (例如 13 月) 时返回 None
+    pub fn to_naive(&self) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(
+            2000 + i32::from(self.year),
+            u32::from(self.month),
+            u32::from(self.day),
+        )?
+        .and_hms_milli_opt(
+            u32::from(self.hour),
+            u32::from(self.minute),
+            u32::from(self.millis / 1000),
+            u32::from(self.millis % 1000),
+        )
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend(self.millis.to_le_bytes());
+        out.push(self.minute & 0x3f | u8::from(self.invalid) << 7);
+        out.push(self.hour & 0x1f | u8::from(self.summer_time) << 7);
+        out.push(self.day & 0x1f | (self.weekday & 0x07) << 5);
+        out.push(self.month & 0x0f);
+        out.push(self.year & 0x7f);
+    }
+}
+
+impl Element {
+    // 该信息元素可以使用的类型标识, 带时标时为 TB/TD/TE/TF
+    pub fn type_id(&self, with_time: bool) -> TypeId {
+        match (self, with_time) {
+            (Element::SinglePoint { .. }, false) => TypeId::SinglePoint,
+            (Element::SinglePoint { .. }, true) => TypeId::SinglePointTime,
+            (Element::DoublePoint { .. }, false) => TypeId::DoublePoint,
+            (Element::DoublePoint { .. }, true) => TypeId::DoublePointTime,
+            (Element::Normalized { .. }, false) => TypeId::Normalized,
+            (Element::Normalized { .. }, true) => TypeId::NormalizedTime,
+            (Element::Scaled { .. }, false) => TypeId::Scaled,
+            (Element::Scaled { .. }, true) => TypeId::ScaledTime,
+            (Element::ShortFloat { .. }, false) => TypeId::ShortFloat,
+            (Element::ShortFloat { .. }, true) => TypeId::ShortFloatTime,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Element::SinglePoint { value, quality } => out.push(quality.0 & 0xf0 | u8::from(value)),
+            Element::DoublePoint { value, quality } => out.push(quality.0 & 0xf0 | value as u8),
+            Element::Normalized { value, quality } | Element::Scaled { value, quality } => {
+                out.extend(value.to_le_bytes());
+                out.push(quality.0);
+            }
+            Element::ShortFloat { value, quality } => {
+                out.extend(value.to_le_bytes());
+                out.push(quality.0);
+            }
+        }
+    }
+}
+
+impl Asdu {
+    pub fn new(type_id: TypeId, cot: Cot, common_address: u16) -> Self {
+        Asdu {
+            type_id,
+            sq: false,
+            count: 0,
+            cot,
+            common_address,
+            objects: Vec::new(),
+            raw: Vec::new(),
+        }
+    }
+
+    // SQ=1 时各对象的地址必须连续
+    pub fn sequence(mut self, sq: bool) -> Self {
+        self.sq = sq;
+        self
+    }
+
+    pub fn object(mut self, address: u32, element: Element, time: Option<Cp56Time2a>) -> Self {
+        self.objects.push(InformationObject {
+            address,
+            element,
+            time,
+        });
+        self.count = self.objects.len() as u8;
+        self
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> Iec104Error {
+        Iec104Error::InvalidAsdu {
+            type_id: self.type_id,
+            message: message.into(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), Iec104Error> {
+        if self.count > 0x7f {
+            return Err(self.invalid(format!("{} objects exceed 127", self.count)));
+        }
+        if !self.type_id.is_supported() {
+            return Ok(());
+        }
+        if usize::from(self.count) != self.objects.len() {
+            return Err(self.invalid(format!(
+                "count {} does not match {} objects",
+                self.count,
+                self.objects.len()
+            )));
+        }
+        for (at, object) in self.objects.iter().enumerate() {
+            if object.element.type_id(self.type_id.has_time()) != self.type_id {
+                return Err(self.invalid(format!("object {at} has a different element type")));
+            }
+            if object.time.is_some() != self.type_id.has_time() {
+                return Err(self.invalid(format!("object {at} time tag does not match type")));
+            }
+            if object.address > 0xff_ffff {
+                return Err(self.invalid(format!("address {} exceeds 3 octets", object.address)));
+            }
+            if self.sq && object.address != self.objects[0].address + at as u32 {
+                return Err(self.invalid("SQ=1 requires consecutive addresses"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<(), Iec104Error> {
+        self.validate()?;
+        out.push(self.type_id.into());
+        out.push(u8::from(self.sq) << 7 | self.count);
+        out.extend(self.cot.encode());
+        out.extend(self.common_address.to_le_bytes());
+
+        for (at, object) in self.objects.iter().enumerate() {
+            if !self.sq || at == 0 {
+                out.extend(&object.address.to_le_bytes()[..3]);
+            }
+            object.element.encode(out);
+            if let Some(time) = &object.time {
+                time.encode(out);
+            }
+        }
+        out.extend(&self.raw);
+        Ok(())
+    }
+}
+
+impl Apdu {
+    pub fn i(send_seq: u16, recv_seq: u16, asdu: Asdu) -> Self {
+        Apdu::I {
+            send_seq,
+            recv_seq,
+            asdu,
+        }
+    }
+
+    pub fn s(recv_seq: u16) -> Self {
+        Apdu::S { recv_seq }
+    }
+
+    pub fn u(function: UFunction) -> Self {
+        Apdu::U(function)
+    }
+
+    // 出错时 out 保持不变
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<(), Iec104Error> {
+        let mut body = Vec::new();
+        match self {
+            Apdu::I {
+                send_seq,
+                recv_seq,
+                asdu,
+            } => {
+                body.extend(encode_seq(*send_seq)?);
+                body.extend(encode_seq(*recv_seq)?);
+                asdu.encode(&mut body)?;
+            }
+            Apdu::S { recv_seq } => {
+                body.extend([0x01, 0x00]);
+                body.extend(encode_seq(*recv_seq)?);
+            }
+            Apdu::U(function) => body.extend([*function as u8, 0, 0, 0]),
+        }
+
+        if body.len() > MAX_APDU_LENGTH {
+            return Err(Iec104Error::TooLong(body.len()));
+        }
+        out.extend([START, body.len() as u8]);
+        out.extend(body);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Iec104Error> {
+        let mut out = Vec::new();
+        self.encode(&mut out)?;
+        Ok(out)
+    }
+}
+
+// 序号只有 15 位, 左移一位后放在控制域中
+fn encode_seq(seq: u16) -> Result<[u8; 2], Iec104Error> {
+    if seq > MAX_SEQ {
+        return Err(Iec104Error::SequenceOutOfRange(seq));
+    }
+    Ok((seq << 1).to_le_bytes())
+}
+
+// 编码为日志中的十六进制 payload, 多个 APDU 首尾相接
+pub fn encode_payload(apdus: &[Apdu]) -> Result<String, Iec104Error> {
+    let mut bytes = Vec::new();
+    for apdu in apdus {
+        apdu.encode(&mut bytes)?;
+    }
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Received => write!(f, "R"),
+            Direction::Sent => write!(f, "S"),
+        }
+    }
+}
+
+// 与 parse_log 的输入格式一致, 例如
+// `2024-08-26 00:00:00.720  [117.132.198.154:3649#172.19.85.132:5003] R:6804...`
+impl fmt::Display for IotLog<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  [{}#{}] {}:{}",
+            self.data_time, self.client_socket, self.server_socket, self.marker, self.payload
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Qds, decode_payload};
+
+    #[test]
+    fn test_encode() {
+        let asdu = Asdu::new(TypeId::ShortFloat, Cot::new(3), 3).object(
+            0x42c0,
+            Element::ShortFloat {
+                value: f32::from_le_bytes([0x9c, 0xc4, 0xde, 0x42]),
+                quality: Qds(0),
+            },
+            None,
+        );
+        assert_eq!(
+            encode_payload(&[Apdu::i(9263, 82, asdu)]).unwrap(),
+            "68125e48a4000d0103000300c042009cc4de4200"
+        );
+        assert_eq!(
+            encode_payload(&[Apdu::s(5), Apdu::u(UFunction::TestFrAct)]).unwrap(),
+            "680401000a00680443000000"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(encode_payload(&[Apdu::s(0x7fff)]).unwrap(), "68040100feff");
+        assert_eq!(
+            Apdu::s(0x8000).to_bytes(),
+            Err(Iec104Error::SequenceOutOfRange(0x8000))
+        );
+        let asdu = Asdu::new(TypeId::SinglePoint, Cot::new(3), 1);
+        assert_eq!(
+            Apdu::i(0, 0xffff, asdu).to_bytes(),
+            Err(Iec104Error::SequenceOutOfRange(0xffff))
+        );
+
+        let time = |year| {
+            NaiveDate::from_ymd_opt(year, 12, 31)
+                .and_then(|date| date.and_hms_opt(23, 59, 59))
+                .unwrap()
+        };
+        assert_eq!(Cp56Time2a::from_naive(time(2099)).unwrap().year, 99);
+        assert_eq!(Cp56Time2a::from_naive(time(2000)).unwrap().year, 0);
+        for year in [1999, 2100, 2256] {
+            assert_eq!(
+                Cp56Time2a::from_naive(time(year)),
+                Err(Iec104Error::TimeOutOfRange(time(year)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_asdu() {
+        let point = Element::SinglePoint {
+            value: true,
+            quality: Qds(0),
+        };
+        let asdu = Asdu::new(TypeId::ShortFloat, Cot::new(3), 1).object(1, point, None);
+        assert!(matches!(
+            Apdu::i(0, 0, asdu).to_bytes(),
+            Err(Iec104Error::InvalidAsdu { .. })
+        ));
+
+        let asdu = Asdu::new(TypeId::SinglePoint, Cot::new(20), 1)
+            .sequence(true)
+            .object(1, point, None)
+            .object(3, point, None);
+        assert!(Apdu::i(0, 0, asdu).to_bytes().is_err());
+
+        let value = Element::ShortFloat {
+            value: 1.0,
+            quality: Qds(0),
+        };
+        let mut asdu = Asdu::new(TypeId::ShortFloat, Cot::new(20), 1);
+        for address in 0..32 {
+            asdu = asdu.object(address, value, None);
+        }
+        assert_eq!(
+            Apdu::i(0, 0, asdu).to_bytes(),
+            Err(Iec104Error::TooLong(4 + 6 + 32 * 8))
+        );
+    }
+
+    #[test]
+    fn test_time_round_trip() {
+        let time = NaiveDate::from_ymd_opt(2024, 8, 26)
+            .and_then(|date| date.and_hms_milli_opt(12, 2, 1, 720))
+            .unwrap();
+        let tag = Cp56Time2a::from_naive(time).unwrap();
+        assert_eq!(tag.weekday, 1);
+        assert_eq!(tag.to_string(), "2024-08-26 12:02:01.720");
+        assert_eq!(tag.to_naive(), Some(time));
+
+        let asdu = Asdu::new(TypeId::ScaledTime, Cot::new(3), 1).object(
+            7,
+            Element::Scaled {
+                value: -42,
+                quality: Qds(0x80),
+            },
+            Some(tag),
+        );
+        let apdus = vec![Apdu::i(1, 2, asdu)];
+        assert_eq!(decode_payload(&encode_payload(&apdus).unwrap()), Ok(apdus));
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::VecDeque;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::{
+    Apdu, Asdu, Cot, Cp56Time2a, Direction, DoublePoint, Element, IotLog, Qds, Socket, UFunction,
+    encode_payload,
+};
+
+// xorshift64*, 同一个种子生成同样的报文
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // 种子为 0 时 xorshift 会一直输出 0
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // [0, n) 内的随机数
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+fn random_element(rng: &mut Rng, kind: u64) -> Element {
+    // 测量值的品质可带 OV 位, 遥信只有高四位
+    let quality = [0x00, 0x00, 0x00, 0x01, 0x10, 0x20, 0x40, 0x80][rng.below(8) as usize];
+    match kind {
+        0 => Element::SinglePoint {
+            value: rng.chance(50),
+            quality: Qds(quality & 0xf0),
+        },
+        1 => Element::DoublePoint {
+            value: [
+                DoublePoint::Intermediate,
+                DoublePoint::Off,
+                DoublePoint::On,
+                DoublePoint::Indeterminate,
+            ][rng.below(4) as usize],
+            quality: Qds(quality & 0xf0),
+        },
+        2 => Element::Normalized {
+            value: rng.next_u64() as i16,
+            quality: Qds(quality),
+        },
+        3 => Element::Scaled {
+            value: rng.next_u64() as i16,
+            quality: Qds(quality),
+        },
+        _ => Element::ShortFloat {
+            value: rng.below(2_000_000) as f32 / 100.0 - 10_000.0,
+            quality: Qds(quality),
+        },
+    }
+}
+
+// 随机的监视方向 ASDU, 带时标的对象使用 time
+// 时间超出 CP56Time2a 的范围时不带时标
+pub fn random_asdu(rng: &mut Rng, common_address: u16, time: NaiveDateTime) -> Asdu {
+    let kind = rng.below(5);
+    let tag = Cp56Time2a::from_naive(time).ok();
+    let with_time = rng.chance(30) && tag.is_some();
+    let sq = rng.chance(30);
+    let count = 1 + rng.below(4) as u32;
+    let cause = if rng.chance(50) { 3 } else { 1 };
+
+    let type_id = random_element(rng, kind).type_id(with_time);
+    let mut asdu = Asdu::new(type_id, Cot::new(cause), common_address).sequence(sq);
+    let mut address = 1 + rng.below(0x4000) as u32;
+    for _ in 0..count {
+        asdu = asdu.object(
+            address,
+            random_element(rng, kind),
+            tag.filter(|_| with_time),
+        );
+        address += if sq { 1 } else { 1 + rng.below(16) as u32 };
+    }
+    asdu
+}
+
+#[derive(Debug, Clone)]
+pub struct TrafficConfig<'a> {
+    // 被控站, 日志中 R 方向
+    pub client: Socket<'a>,
+    // 主站, 日志中 S 方向
+    pub server: Socket<'a>,
+    pub start: NaiveDateTime,
+    // 两个 I 帧之间的间隔
+    pub interval: TimeDelta,
+    pub common_address: u16,
+    // 主站每收到 w 个 I 帧回一个 S 帧
+    pub w: usize,
+    // 每隔多少个 I 帧做一次 TESTFR, 0 表示不做
+    pub testfr_every: usize,
+    pub seed: u64,
+}
+
+impl<'a> TrafficConfig<'a> {
+    pub fn new(client: Socket<'a>, server: Socket<'a>, start: NaiveDateTime) -> Self {
+        TrafficConfig {
+            client,
+            server,
+            start,
+            interval: TimeDelta::milliseconds(200),
+            common_address: 1,
+            w: 8,
+            testfr_every: 50,
+            seed: 0,
+        }
+    }
+}
+
+// 生成一条 104 链路的日志行: STARTDT 之后被控站上送 I 帧, 主站按 w 确认并定期 TESTFR
+#[derive(Debug)]
+pub struct TrafficGenerator<'a> {
+    config: TrafficConfig<'a>,
+    rng: Rng,
+    time: NaiveDateTime,
+    send_seq: u16,
+    unacknowledged: usize,
+    sent: usize,
+    lines: VecDeque<String>,
+}
+
+impl<'a> TrafficGenerator<'a> {
+    pub fn new(config: TrafficConfig<'a>) -> Self {
+        let mut generator = TrafficGenerator {
+            rng: Rng::new(config.seed),
+            time: config.start,
+            send_seq: 0,
+            unacknowledged: 0,
+            sent: 0,
+            lines: VecDeque::new(),
+            config,
+        };
+        generator.push(Direction::Sent, Apdu::u(UFunction::StartDtAct));
+        generator.push(Direction::Received, Apdu::u(UFunction::StartDtCon));
+        generator
+    }
+
+    fn push(&mut self, direction: Direction, apdu: Apdu) {
+        let data_time = self.time.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+        // 生成的 ASDU 总是合法的
+        let payload = encode_payload(&[apdu]).expect("generated APDU encodes");
+        let marker = direction.to_string();
+        let log = IotLog {
+            data_time: &data_time,
+            client_socket: self.config.client,
+            server_socket: self.config.server,
+            marker: &marker,
+            payload: &payload,
+        };
+        self.lines.push_back(log.to_string());
+        self.time += TimeDelta::milliseconds(10);
+    }
+
+    fn step(&mut self) {
+        self.time += self.config.interval;
+        let asdu = random_asdu(&mut self.rng, self.config.common_address, self.time);
+        self.push(Direction::Received, Apdu::i(self.send_seq, 0, asdu));
+        self.send_seq = (self.send_seq + 1) % (1 << 15);
+        self.unacknowledged += 1;
+        self.sent += 1;
+
+        if self.unacknowledged >= self.config.w {
+            self.push(Direction::Sent, Apdu::s(self.send_seq));
+            self.unacknowledged = 0;
+        }
+        if self.config.testfr_every > 0 && self.sent.is_multiple_of(self.config.testfr_every) {
+            self.push(Direction::Sent, Apdu::u(UFunction::TestFrAct));
+            self.push(Direction::Received, Apdu::u(UFunction::TestFrCon));
+        }
+    }
+}
+
+impl Iterator for TrafficGenerator<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.lines.is_empty() {
+            self.step();
+        }
+        self.lines.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Host, Iec104Error, SessionAnalyzer, SessionConfig, TypeId, decode_payload, parse_log,
+    };
+    use chrono::NaiveDate;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn start() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 26)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .unwrap()
+    }
+
+    fn seq() -> impl Strategy<Value = u16> {
+        prop_oneof![Just(0), Just(0x7fff), 0..=0x7fffu16]
+    }
+
+    // CP56Time2a 能表示的时间, 包括 2000 和 2099 年
+    fn time() -> impl Strategy<Value = NaiveDateTime> {
+        let year = prop_oneof![Just(2000), Just(2099), 2000..=2099i32];
+        (
+            year,
+            1..=12u32,
+            1..=28u32,
+            0..24u32,
+            0..60u32,
+            0..60u32,
+            0..1000u32,
+        )
+            .prop_map(|(year, month, day, hour, minute, second, millis)| {
+                NaiveDate::from_ymd_opt(year, month, day)
+                    .and_then(|date| date.and_hms_milli_opt(hour, minute, second, millis))
+                    .unwrap()
+            })
+    }
+
+    fn cot() -> impl Strategy<Value = Cot> {
+        (0..64u8, any::<bool>(), any::<bool>(), any::<u8>()).prop_map(
+            |(cause, negative, test, originator)| Cot {
+                cause,
+                negative,
+                test,
+                originator,
+            },
+        )
+    }
+
+    // 遥信的品质只有高四位
+    fn element(kind: u8) -> BoxedStrategy<Element> {
+        let point_quality = || any::<u8>().prop_map(|quality| Qds(quality & 0xf0));
+        let quality = || any::<u8>().prop_map(Qds);
+        match kind {
+            0 => (any::<bool>(), point_quality())
+                .prop_map(|(value, quality)| Element::SinglePoint { value, quality })
+                .boxed(),
+            1 => (0..4u8, point_quality())
+                .prop_map(|(bits, quality)| Element::DoublePoint {
+                    value: [
+                        DoublePoint::Intermediate,
+                        DoublePoint::Off,
+                        DoublePoint::On,
+                        DoublePoint::Indeterminate,
+                    ][usize::from(bits)],
+                    quality,
+                })
+                .boxed(),
+            2 => (any::<i16>(), quality())
+                .prop_map(|(value, quality)| Element::Normalized { value, quality })
+                .boxed(),
+            3 => (any::<i16>(), quality())
+                .prop_map(|(value, quality)| Element::Scaled { value, quality })
+                .boxed(),
+            _ => (any::<f32>().prop_filter("NaN", |v| !v.is_nan()), quality())
+                .prop_map(|(value, quality)| Element::ShortFloat { value, quality })
+                .boxed(),
+        }
+    }
+
+    // 支持的类型标识; 最多 16 个对象, 最长的 M_ME_TF_1 也不超过 253 字节
+    fn asdu() -> impl Strategy<Value = Asdu> {
+        (
+            0..5u8,
+            any::<bool>(),
+            any::<bool>(),
+            1..=16usize,
+            cot(),
+            any::<u16>(),
+        )
+            .prop_flat_map(|(kind, with_time, sq, count, cot, common_address)| {
+                let object = (element(kind), 0..=0xff_ffefu32, time());
+                vec(object, count).prop_map(move |objects| {
+                    let type_id = objects[0].0.type_id(with_time);
+                    let first = objects[0].1;
+                    let mut asdu = Asdu::new(type_id, cot, common_address).sequence(sq);
+                    for (at, (element, address, time)) in objects.into_iter().enumerate() {
+                        let address = if sq { first + at as u32 } else { address };
+                        let tag = with_time.then(|| Cp56Time2a::from_naive(time).unwrap());
+                        asdu = asdu.object(address, element, tag);
+                    }
+                    asdu
+                })
+            })
+    }
+
+    // 不支持的类型标识, 信息体原样保留; 控制域和 ASDU 头之后最多 243 字节
+    fn raw_asdu() -> impl Strategy<Value = Asdu> {
+        let type_id = any::<u8>()
+            .prop_map(TypeId::from)
+            .prop_filter("unsupported", |id| !id.is_supported());
+        (
+            type_id,
+            any::<bool>(),
+            0..=0x7fu8,
+            cot(),
+            any::<u16>(),
+            vec(any::<u8>(), 0..=243),
+        )
+            .prop_map(|(type_id, sq, count, cot, common_address, raw)| Asdu {
+                type_id,
+                sq,
+                count,
+                cot,
+                common_address,
+                objects: Vec::new(),
+                raw,
+            })
+    }
+
+    fn apdu() -> impl Strategy<Value = Apdu> {
+        let function = prop::sample::select(vec![
+            UFunction::StartDtAct,
+            UFunction::StartDtCon,
+            UFunction::StopDtAct,
+            UFunction::StopDtCon,
+            UFunction::TestFrAct,
+            UFunction::TestFrCon,
+        ]);
+        prop_oneof![
+            seq().prop_map(Apdu::s),
+            function.prop_map(Apdu::u),
+            (seq(), seq(), prop_oneof![asdu(), raw_asdu()])
+                .prop_map(|(send_seq, recv_seq, asdu)| Apdu::i(send_seq, recv_seq, asdu)),
+        ]
+    }
+
+    proptest! {
+        // 随机报文编码后再解码应得到原值
+        #[test]
+        fn test_round_trip(apdus in vec(apdu(), 1..4)) {
+            let payload = encode_payload(&apdus).unwrap();
+            prop_assert_eq!(decode_payload(&payload), Ok(apdus), "{}", payload);
+        }
+
+        #[test]
+        fn test_seq_out_of_range(seq in 0x8000..=u16::MAX) {
+            prop_assert_eq!(
+                Apdu::s(seq).to_bytes(),
+                Err(Iec104Error::SequenceOutOfRange(seq))
+            );
+        }
+
+        #[test]
+        fn test_year_out_of_range(year in prop_oneof![1900..2000i32, 2100..2300i32]) {
+            let time = NaiveDate::from_ymd_opt(year, 1, 1)
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .unwrap();
+            prop_assert_eq!(
+                Cp56Time2a::from_naive(time),
+                Err(Iec104Error::TimeOutOfRange(time))
+            );
+        }
+    }
+
+    #[test]
+    fn test_generator() {
+        let client = Socket {
+            host: Host::Ip([10, 0, 0, 1].into()),
+            port: 3649,
+        };
+        let server = Socket {
+            host: Host::Ip([10, 0, 0, 2].into()),
+            port: 2404,
+        };
+        let mut config = TrafficConfig::new(client, server, start());
+        config.seed = 7;
+        config.testfr_every = 20;
+
+        let lines: Vec<String> = TrafficGenerator::new(config.clone()).take(300).collect();
+        // 同一个种子生成相同的日志
+        assert_eq!(
+            lines,
+            TrafficGenerator::new(config).take(300).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            lines[0],
+            "2024-08-26 00:00:00.000  [10.0.0.1:3649#10.0.0.2:2404] S:680407000000"
+        );
+
+        // 生成的日志可以被 parse_log 解析并按原样输出, 链路上没有异常
+        let mut analyzer = SessionAnalyzer::new(SessionConfig::default());
+        for line in &lines {
+            let mut input = line.as_str();
+            let log = parse_log(&mut input).unwrap();
+            assert_eq!(&log.to_string(), line);
+            analyzer.observe(&log).unwrap();
+        }
+        assert_eq!(analyzer.events(), []);
+        let (_, stats) = analyzer.sessions().next().unwrap();
+        assert!(stats.i_frames > 200);
+        assert_eq!(stats.testfr_timeouts, 0);
+    }
+}
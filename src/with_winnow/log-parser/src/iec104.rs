@@ -0,0 +1,772 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+use winnow::Parser;
+use winnow::binary::{le_f32, le_i16, le_u16, le_u24, length_and_then, u8};
+use winnow::combinator::{eof, fail, repeat, terminated};
+use winnow::error::{StrContext, StrContextValue};
+use winnow::prelude::*;
+use winnow::token::take;
+
+use crate::IotLog;
+
+// APDU 起始字节
+pub const START: u8 = 0x68;
+
+// U 帧的功能, 值为控制域第一个字节
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum UFunction {
+    StartDtAct = 0x07,
+    StartDtCon = 0x0b,
+    StopDtAct = 0x13,
+    StopDtCon = 0x23,
+    TestFrAct = 0x43,
+    TestFrCon = 0x83,
+}
+
+impl UFunction {
+    fn from_control(control: u8) -> Option<Self> {
+        match control {
+            0x07 => Some(UFunction::StartDtAct),
+            0x0b => Some(UFunction::StartDtCon),
+            0x13 => Some(UFunction::StopDtAct),
+            0x23 => Some(UFunction::StopDtCon),
+            0x43 => Some(UFunction::TestFrAct),
+            0x83 => Some(UFunction::TestFrCon),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UFunction::StartDtAct => "STARTDT act",
+            UFunction::StartDtCon => "STARTDT con",
+            UFunction::StopDtAct => "STOPDT act",
+            UFunction::StopDtCon => "STOPDT con",
+            UFunction::TestFrAct => "TESTFR act",
+            UFunction::TestFrCon => "TESTFR con",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// 类型标识, 只解码常用的监视方向类型
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TypeId {
+    // M_SP_NA_1
+    SinglePoint,
+    // M_DP_NA_1
+    DoublePoint,
+    // M_ME_NA_1
+    Normalized,
+    // M_ME_NB_1
+    Scaled,
+    // M_ME_NC_1
+    ShortFloat,
+    // M_SP_TB_1
+    SinglePointTime,
+    // M_DP_TB_1
+    DoublePointTime,
+    // M_ME_TD_1
+    NormalizedTime,
+    // M_ME_TE_1
+    ScaledTime,
+    // M_ME_TF_1
+    ShortFloatTime,
+    Other(u8),
+}
+
+impl TypeId {
+    // 信息元素后是否带 CP56Time2a 时标
+    pub fn has_time(self) -> bool {
+        matches!(
+            self,
+            TypeId::SinglePointTime
+                | TypeId::DoublePointTime
+                | TypeId::NormalizedTime
+                | TypeId::ScaledTime
+                | TypeId::ShortFloatTime
+        )
+    }
+
+    pub fn is_supported(self) -> bool {
+        !matches!(self, TypeId::Other(_))
+    }
+}
+
+impl From<u8> for TypeId {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => TypeId::SinglePoint,
+            3 => TypeId::DoublePoint,
+            9 => TypeId::Normalized,
+            11 => TypeId::Scaled,
+            13 => TypeId::ShortFloat,
+            30 => TypeId::SinglePointTime,
+            31 => TypeId::DoublePointTime,
+            34 => TypeId::NormalizedTime,
+            35 => TypeId::ScaledTime,
+            36 => TypeId::ShortFloatTime,
+            id => TypeId::Other(id),
+        }
+    }
+}
+
+impl From<TypeId> for u8 {
+    fn from(id: TypeId) -> Self {
+        match id {
+            TypeId::SinglePoint => 1,
+            TypeId::DoublePoint => 3,
+            TypeId::Normalized => 9,
+            TypeId::Scaled => 11,
+            TypeId::ShortFloat => 13,
+            TypeId::SinglePointTime => 30,
+            TypeId::DoublePointTime => 31,
+            TypeId::NormalizedTime => 34,
+            TypeId::ScaledTime => 35,
+            TypeId::ShortFloatTime => 36,
+            TypeId::Other(id) => id,
+        }
+    }
+}
+
+impl fmt::Display for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TypeId::SinglePoint => "M_SP_NA_1",
+            TypeId::DoublePoint => "M_DP_NA_1",
+            TypeId::Normalized => "M_ME_NA_1",
+            TypeId::Scaled => "M_ME_NB_1",
+            TypeId::ShortFloat => "M_ME_NC_1",
+            TypeId::SinglePointTime => "M_SP_TB_1",
+            TypeId::DoublePointTime => "M_DP_TB_1",
+            TypeId::NormalizedTime => "M_ME_TD_1",
+            TypeId::ScaledTime => "M_ME_TE_1",
+            TypeId::ShortFloatTime => "M_ME_TF_1",
+            TypeId::Other(id) => return write!(f, "type {id}"),
+        };
+        write!(f, "{name}")
+    }
+}
+
+// 接受助记符 (`M_ME_NC_1`, 不区分大小写) 或数字 (`13`)
+impl FromStr for TypeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse::<u8>() {
+            return Ok(TypeId::from(id));
+        }
+        (0..=u8::MAX)
+            .map(TypeId::from)
+            .filter(|id| id.is_supported())
+            .find(|id| id.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown type id `{s}`"))
+    }
+}
+
+// 传送原因, 104 中占两个字节: 原因 + 源发站地址
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cot {
+    pub cause: u8,
+    pub negative: bool,
+    pub test: bool,
+    pub originator: u8,
+}
+
+impl Cot {
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self.cause {
+            1 => "per/cyc",
+            2 => "back",
+            3 => "spont",
+            4 => "init",
+            5 => "req",
+            6 => "act",
+            7 => "actcon",
+            8 => "deact",
+            9 => "deactcon",
+            10 => "actterm",
+            20 => "inrogen",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+// 品质描述词 QDS, SIQ/DIQ 的高四位与其相同
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct Qds(pub u8);
+
+impl Qds {
+    pub fn overflow(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn blocked(self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn substituted(self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn not_topical(self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn invalid(self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn is_good(self) -> bool {
+        self.0 & 0xf1 == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DoublePoint {
+    Intermediate,
+    Off,
+    On,
+    Indeterminate,
+}
+
+impl DoublePoint {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => DoublePoint::Intermediate,
+            1 => DoublePoint::Off,
+            2 => DoublePoint::On,
+            _ => DoublePoint::Indeterminate,
+        }
+    }
+}
+
+// 信息元素, 规一化值保留原始的 i16, 用 Element::value 换算
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Element {
+    SinglePoint { value: bool, quality: Qds },
+    DoublePoint { value: DoublePoint, quality: Qds },
+    Normalized { value: i16, quality: Qds },
+    Scaled { value: i16, quality: Qds },
+    ShortFloat { value: f32, quality: Qds },
+}
+
+impl Element {
+    // 统一换算成数值, 双点的 on 为 1, off 为 0
+    pub fn value(&self) -> Option<f64> {
+        match *self {
+            Element::SinglePoint { value, .. } => Some(f64::from(u8::from(value))),
+            Element::DoublePoint { value, .. } => match value {
+                DoublePoint::Off => Some(0.0),
+                DoublePoint::On => Some(1.0),
+                _ => None,
+            },
+            Element::Normalized { value, .. } => Some(f64::from(value) / 32768.0),
+            Element::Scaled { value, .. } => Some(f64::from(value)),
+            Element::ShortFloat { value, .. } => Some(f64::from(value)),
+        }
+    }
+
+    pub fn quality(&self) -> Qds {
+        match *self {
+            Element::SinglePoint { quality, .. }
+            | Element::DoublePoint { quality, .. }
+            | Element::Normalized { quality, .. }
+            | Element::Scaled { quality, .. }
+            | Element::ShortFloat { quality, .. } => quality,
+        }
+    }
+}
+
+// 七个八位位组的二进制时间, 年份为 2000 年起的偏移
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cp56Time2a {
+    pub millis: u16,
+    pub minute: u8,
+    pub invalid: bool,
+    pub hour: u8,
+    pub summer_time: bool,
+    pub day: u8,
+    pub weekday: u8,
+    pub month: u8,
+    pub year: u8,
+}
+
+// 例如 `2024-08-26 00:00:00.720`
+impl fmt::Display for Cp56Time2a {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            2000 + u16::from(self.year),
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.millis / 1000,
+            self.millis % 1000
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InformationObject {
+    pub address: u32,
+    pub element: Element,
+    pub time: Option<Cp56Time2a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asdu {
+    pub type_id: TypeId,
+    // VSQ 的 SQ 位: 只有第一个信息对象带地址, 之后地址依次加一
+    pub sq: bool,
+    // VSQ 的信息对象数目
+    pub count: u8,
+    pub cot: Cot,
+    pub common_address: u16,
+    pub objects: Vec<InformationObject>,
+    // 不支持的类型标识, 信息体原样保留
+    pub raw: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Apdu {
+    I {
+        send_seq: u16,
+        recv_seq: u16,
+        asdu: Asdu,
+    },
+    S {
+        recv_seq: u16,
+    },
+    U(UFunction),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Iec104Error {
+    // payload 不是偶数长度的十六进制串
+    InvalidHex(String),
+    // 日志时间不是 `%Y-%m-%d %H:%M:%S%.f` 格式
+    InvalidTime(String),
+    InvalidFrame { offset: usize, message: String },
+    // 编码时 ASDU 的内容与类型标识不符
+    InvalidAsdu { type_id: TypeId, message: String },
+    // 编码后的 APDU 超过 253 字节
+    TooLong(usize),
+    // 会话分析需要 `R` / `S` 标记来区分双方
+    UnknownDirection(String),
+    // 编码时 N(S) / N(R) 超过 15 位
+    SequenceOutOfRange(u16),
+    // CP56Time2a 只能表示 2000..=2099 年
+    TimeOutOfRange(NaiveDateTime),
+}
+
+impl fmt::Display for Iec104Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iec104Error::InvalidHex(hex) => write!(f, "invalid hex payload `{hex}`"),
+            Iec104Error::InvalidTime(time) => write!(f, "invalid log time `{time}`"),
+            Iec104Error::InvalidFrame { offset, message } => {
+                write!(f, "invalid IEC 104 frame at byte {offset}: {message}")
+            }
+            Iec104Error::InvalidAsdu { type_id, message } => {
+                write!(f, "invalid {type_id} ASDU: {message}")
+            }
+            Iec104Error::TooLong(length) => {
+                write!(f, "APDU length {length} exceeds 253 bytes")
+            }
+            Iec104Error::UnknownDirection(marker) => {
+                write!(
+                    f,
+                    "unknown direction marker `{marker}`, expected `R` or `S`"
+                )
+            }
+            Iec104Error::SequenceOutOfRange(seq) => {
+                write!(f, "sequence number {seq} exceeds 32767")
+            }
+            Iec104Error::TimeOutOfRange(time) => {
+                write!(f, "time {time} is outside CP56Time2a years 2000..=2099")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Iec104Error {}
+
+fn parse_cp56time2a(input: &mut &[u8]) -> ModalResult<Cp56Time2a> {
+    let (millis, minute, hour, day, month, year) =
+        (le_u16, u8, u8, u8, u8, u8).parse_next(input)?;
+    Ok(Cp56Time2a {
+        millis,
+        minute: minute & 0x3f,
+        invalid: minute & 0x80 != 0,
+        hour: hour & 0x1f,
+        summer_time: hour & 0x80 != 0,
+        day: day & 0x1f,
+        weekday: day >> 5,
+        month: month & 0x0f,
+        year: year & 0x7f,
+    })
+}
+
+fn parse_element(type_id: TypeId, input: &mut &[u8]) -> ModalResult<Element> {
+    match type_id {
+        TypeId::SinglePoint | TypeId::SinglePointTime => u8
+            .map(|siq| Element::SinglePoint {
+                value: siq & 0x01 != 0,
+                quality: Qds(siq & 0xf0),
+            })
+            .parse_next(input),
+        TypeId::DoublePoint | TypeId::DoublePointTime => u8
+            .map(|diq| Element::DoublePoint {
+                value: DoublePoint::from_bits(diq),
+                quality: Qds(diq & 0xf0),
+            })
+            .parse_next(input),
+        TypeId::Normalized | TypeId::NormalizedTime => (le_i16, u8)
+            .map(|(value, qds)| Element::Normalized {
+                value,
+                quality: Qds(qds),
+            })
+            .parse_next(input),
+        TypeId::Scaled | TypeId::ScaledTime => (le_i16, u8)
+            .map(|(value, qds)| Element::Scaled {
+                value,
+                quality: Qds(qds),
+            })
+            .parse_next(input),
+        TypeId::ShortFloat | TypeId::ShortFloatTime => (le_f32, u8)
+            .map(|(value, qds)| Element::ShortFloat {
+                value,
+                quality: Qds(qds),
+            })
+            .parse_next(input),
+        TypeId::Other(_) => unreachable!("unsupported type id has no elements"),
+    }
+}
+
+fn parse_object(
+    type_id: TypeId,
+    address: u32,
+    input: &mut &[u8],
+) -> ModalResult<InformationObject> {
+    let element = parse_element(type_id, input)?;
+    let time = if type_id.has_time() {
+        Some(parse_cp56time2a.parse_next(input)?)
+    } else {
+        None
+    };
+    Ok(InformationObject {
+        address,
+        element,
+        time,
+    })
+}
+
+fn parse_objects(
+    type_id: TypeId,
+    sq: bool,
+    count: u8,
+    input: &mut &[u8],
+) -> ModalResult<Vec<InformationObject>> {
+    let mut objects = Vec::with_capacity(usize::from(count));
+    if sq {
+        let address = le_u24.parse_next(input)?;
+        for offset in 0..u32::from(count) {
+            objects.push(parse_object(type_id, address + offset, input)?);
+        }
+    } else {
+        for _ in 0..count {
+            let address = le_u24.parse_next(input)?;
+            objects.push(parse_object(type_id, address, input)?);
+        }
+    }
+    Ok(objects)
+}
+
+pub fn parse_asdu(input: &mut &[u8]) -> ModalResult<Asdu> {
+    let type_id = u8.map(TypeId::from).parse_next(input)?;
+    let vsq = u8.parse_next(input)?;
+    let (cause, originator) = (u8, u8).parse_next(input)?;
+    let common_address = le_u16.parse_next(input)?;
+
+    let (sq, count) = (vsq & 0x80 != 0, vsq & 0x7f);
+    let (objects, raw) = if type_id.is_supported() {
+        (parse_objects(type_id, sq, count, input)?, Vec::new())
+    } else {
+        let raw = take(input.len()).parse_next(input)?;
+        (Vec::new(), raw.to_vec())
+    };
+
+    Ok(Asdu {
+        type_id,
+        sq,
+        count,
+        cot: Cot {
+            cause: cause & 0x3f,
+            negative: cause & 0x40 != 0,
+            test: cause & 0x80 != 0,
+            originator,
+        },
+        common_address,
+        objects,
+        raw,
+    })
+}
+
+// 控制域之后的内容, 已按长度截取
+fn parse_body(input: &mut &[u8]) -> ModalResult<Apdu> {
+    let (first, second) = (le_u16, le_u16).parse_next(input)?;
+    let apdu = if first & 0x01 == 0 {
+        Apdu::I {
+            send_seq: first >> 1,
+            recv_seq: second >> 1,
+            asdu: parse_asdu.parse_next(input)?,
+        }
+    } else if first & 0x03 == 0x01 {
+        Apdu::S {
+            recv_seq: second >> 1,
+        }
+    } else {
+        let Some(function) = UFunction::from_control(first as u8) else {
+            return fail
+                .context(StrContext::Label("U format function"))
+                .parse_next(input);
+        };
+        Apdu::U(function)
+    };
+    eof.context(StrContext::Expected(StrContextValue::Description(
+        "end of APDU",
+    )))
+    .parse_next(input)?;
+    Ok(apdu)
+}
+
+pub fn parse_apdu(input: &mut &[u8]) -> ModalResult<Apdu> {
+    START
+        .context(StrContext::Expected(StrContextValue::Description(
+            "start byte 0x68",
+        )))
+        .parse_next(input)?;
+    length_and_then(u8, parse_body).parse_next(input)
+}
+
+// 一段 payload 可能包含多个连续的 APDU
+pub fn parse_apdus(input: &mut &[u8]) -> ModalResult<Vec<Apdu>> {
+    terminated(repeat(1.., parse_apdu), eof).parse_next(input)
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(s.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+// 解码日志中十六进制的 payload
+pub fn decode_payload(hex: &str) -> Result<Vec<Apdu>, Iec104Error> {
+    let hex = hex.trim();
+    let bytes = parse_hex(hex).ok_or_else(|| Iec104Error::InvalidHex(hex.to_string()))?;
+    parse_apdus
+        .parse(bytes.as_slice())
+        .map_err(|e| Iec104Error::InvalidFrame {
+            offset: e.offset(),
+            message: e.inner().to_string(),
+        })
+}
+
+// 单行的报文摘要, 例如 `I(ns=9263, nr=82) M_ME_NC_1 spont ca=3: 17088=111.384`
+impl fmt::Display for Apdu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Apdu::I {
+                send_seq,
+                recv_seq,
+                asdu,
+            } => write!(f, "I(ns={send_seq}, nr={recv_seq}) {asdu}"),
+            Apdu::S { recv_seq } => write!(f, "S(nr={recv_seq})"),
+            Apdu::U(function) => write!(f, "U({function})"),
+        }
+    }
+}
+
+impl fmt::Display for Asdu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.type_id)?;
+        match self.cot.name() {
+            Some(name) => write!(f, " {name}")?,
+            None => write!(f, " cot={}", self.cot.cause)?,
+        }
+        if self.cot.negative {
+            write!(f, " neg")?;
+        }
+        if self.cot.test {
+            write!(f, " test")?;
+        }
+        write!(f, " ca={}", self.common_address)?;
+        if !self.type_id.is_supported() {
+            return write!(f, " ({} bytes)", self.raw.len());
+        }
+        for (at, object) in self.objects.iter().enumerate() {
+            write!(f, "{}{object}", if at == 0 { ": " } else { ", " })?;
+        }
+        Ok(())
+    }
+}
+
+// 地址=值, 品质不好时附加标志, 带时标时附加 @时间
+impl fmt::Display for InformationObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.address)?;
+        match self.element {
+            Element::DoublePoint { value, .. } => write!(f, "{value:?}")?,
+            // 按 f32 输出, 避免转换为 f64 后多出的尾数
+            Element::ShortFloat { value, .. } => write!(f, "{value}")?,
+            element => match element.value() {
+                Some(value) => write!(f, "{value}")?,
+                None => write!(f, "?")?,
+            },
+        }
+        let quality = self.element.quality();
+        if !quality.is_good() {
+            write!(f, " {quality}")?;
+        }
+        if let Some(time) = &self.time {
+            write!(f, " @{time}")?;
+        }
+        Ok(())
+    }
+}
+
+// 例如 `nt|iv`, 品质正常时为空
+impl fmt::Display for Qds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.overflow(), "ov"),
+            (self.blocked(), "bl"),
+            (self.substituted(), "sb"),
+            (self.not_topical(), "nt"),
+            (self.invalid(), "iv"),
+        ];
+        let names: Vec<&str> = flags
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .collect();
+        write!(f, "{}", names.join("|"))
+    }
+}
+
+impl IotLog<'_> {
+    pub fn apdus(&self) -> Result<Vec<Apdu>, Iec104Error> {
+        decode_payload(self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_frame() {
+        let apdus = decode_payload("68125e48a4000d0103000300c042009cc4de4200").unwrap();
+        assert_eq!(apdus.len(), 1);
+        let Apdu::I {
+            send_seq,
+            recv_seq,
+            asdu,
+        } = &apdus[0]
+        else {
+            panic!("expected I format");
+        };
+        assert_eq!((*send_seq, *recv_seq), (9263, 82));
+        assert_eq!(asdu.type_id, TypeId::ShortFloat);
+        assert_eq!("m_me_nc_1".parse(), Ok(TypeId::ShortFloat));
+        assert_eq!("100".parse(), Ok(TypeId::Other(100)));
+        assert_eq!((asdu.sq, asdu.count), (false, 1));
+        assert_eq!(asdu.cot.name(), Some("spont"));
+        assert_eq!(asdu.common_address, 3);
+
+        let object = &asdu.objects[0];
+        assert_eq!(object.address, 0x42c0);
+        assert_eq!(object.time, None);
+        assert!(object.element.quality().is_good());
+        let value = object.element.value().unwrap();
+        assert!((value - 111.384_00).abs() < 1e-3, "{value}");
+        assert_eq!(
+            apdus[0].to_string(),
+            "I(ns=9263, nr=82) M_ME_NC_1 spont ca=3: 17088=111.384"
+        );
+    }
+
+    #[test]
+    fn test_s_and_u_frames() {
+        assert_eq!(
+            decode_payload("680401000a00").unwrap(),
+            [Apdu::S { recv_seq: 5 }]
+        );
+        // 一段 payload 中的多个 APDU
+        assert_eq!(
+            decode_payload("68040b000000680443000000").unwrap(),
+            [
+                Apdu::U(UFunction::StartDtCon),
+                Apdu::U(UFunction::TestFrAct)
+            ]
+        );
+        assert!(decode_payload("680403000000").is_err());
+    }
+
+    #[test]
+    fn test_sequence_with_time() {
+        // M_SP_TB_1, SQ=1 两个对象, 地址 0x000001 起
+        let hex = "681d020004001e828100010001000000e803020c3a081801e803020c3a0818";
+        let apdus = decode_payload(hex).unwrap();
+        let Apdu::I { asdu, .. } = &apdus[0] else {
+            panic!("expected I format");
+        };
+        assert_eq!(asdu.type_id, TypeId::SinglePointTime);
+        assert!(asdu.sq);
+        assert_eq!(asdu.cot.name(), Some("per/cyc"));
+        assert!(asdu.cot.test);
+        assert_eq!(
+            asdu.objects
+                .iter()
+                .map(|object| object.address)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(
+            asdu.objects[1].element,
+            Element::SinglePoint {
+                value: true,
+                quality: Qds(0),
+            }
+        );
+        assert_eq!(asdu.objects[0].to_string(), "1=0 @2024-08-26 12:02:01.000");
+        let time = asdu.objects[0].time.unwrap();
+        assert_eq!(time.to_string(), "2024-08-26 12:02:01.000");
+    }
+
+    #[test]
+    fn test_invalid_payload() {
+        assert_eq!(
+            decode_payload("68f"),
+            Err(Iec104Error::InvalidHex("68f".into()))
+        );
+        assert!(matches!(
+            decode_payload("69040b000000"),
+            Err(Iec104Error::InvalidFrame { offset: 0, .. })
+        ));
+        // 长度超出实际字节
+        assert!(decode_payload("68125e48a400").is_err());
+    }
+}
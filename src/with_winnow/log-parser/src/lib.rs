@@ -1,5 +1,15 @@
+mod encode;
+mod generate;
+mod iec104;
 mod parser;
+mod session;
+mod summary;
 mod token;
 
+pub use encode::*;
+pub use generate::*;
+pub use iec104::*;
 pub use parser::parse_log;
+pub use session::*;
+pub use summary::*;
 pub use token::*;
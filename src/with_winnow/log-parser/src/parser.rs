@@ -1,14 +1,22 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
 use winnow::Parser;
 use winnow::ascii::digit1;
 use winnow::ascii::space1;
 use winnow::ascii::till_line_ending;
+use winnow::combinator::alt;
+use winnow::combinator::delimited;
 use winnow::combinator::preceded;
 use winnow::combinator::separated;
 use winnow::combinator::seq;
+use winnow::error::StrContext;
 use winnow::prelude::*;
 use winnow::token::one_of;
+use winnow::token::take_till;
 use winnow::token::take_until;
+use winnow::token::take_while;
 
+use crate::Host;
 use crate::IotLog;
 use crate::Socket;
 
@@ -20,26 +28,62 @@ fn parse_datetime<'s>(s: &mut &'s str) -> ModalResult<&'s str> {
     parsed.parse_next(s)
 }
 
-fn parse_socket<'a>(s: &mut &'a str) -> ModalResult<Socket<'a>> {
-    // 解析 IP 地址
-    let mut parsed_ip = separated(1.., digit1, '.').map(|()| ()).take();
-    // 解析端口号
-    let mut parsed_port = digit1.map(|port: &str| port.parse::<u16>().unwrap());
+// 主机名的每一段为字母、数字和 `-`, 不能以 `-` 开头或结尾
+fn is_hostname(name: &str) -> bool {
+    name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+}
+
+// 解析主机: `[IPv6]`, IPv4 或主机名; 全为数字和点时必须是合法的 IPv4
+fn parse_host<'a>(s: &mut &'a str) -> ModalResult<Host<'a>> {
+    let ipv6 = delimited('[', take_till(1.., ']'), ']')
+        .try_map(|ip: &str| ip.parse::<Ipv6Addr>())
+        .map(|ip| Host::Ip(IpAddr::V6(ip)));
+    let ipv4_or_name = take_while(1.., |c: char| {
+        c.is_ascii_alphanumeric() || c == '.' || c == '-'
+    })
+    .verify_map(|host: &'a str| {
+        if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            host.parse::<Ipv4Addr>()
+                .ok()
+                .map(|ip| Host::Ip(IpAddr::V4(ip)))
+        } else {
+            is_hostname(host).then_some(Host::Name(host))
+        }
+    });
+
+    alt((ipv6, ipv4_or_name))
+        .context(StrContext::Label("host"))
+        .parse_next(s)
+}
+
+pub fn parse_socket<'a>(s: &mut &'a str) -> ModalResult<Socket<'a>> {
+    // 端口号超过 65535 时返回错误
+    let mut port = digit1
+        .try_map(str::parse::<u16>)
+        .context(StrContext::Label("port"));
 
     seq!(Socket {
-        ip_address: parsed_ip,
+        host: parse_host,
         _: ':',
-        port: parsed_port,
+        port: port,
     })
     .parse_next(s)
 }
 
+// 解析 `]` 之后、`:` 之前的标记, 例如 ` R`
+fn parse_marker<'a>(s: &mut &'a str) -> ModalResult<&'a str> {
+    take_until(1.., ":").map(str::trim).parse_next(s)
+}
+
 // 解析 payload 荷载
 fn parse_payload<'a>(s: &mut &'a str) -> ModalResult<&'a str> {
-    let parsed_payload = preceded(':', till_line_ending);
-    let mut parsed_payload = preceded(take_until(1.., ":"), parsed_payload);
-
-    parsed_payload.parse_next(s)
+    preceded(':', till_line_ending).parse_next(s)
 }
 
 pub fn parse_log<'a>(s: &mut &'a str) -> ModalResult<IotLog<'a>> {
@@ -52,8 +96,64 @@ pub fn parse_log<'a>(s: &mut &'a str) -> ModalResult<IotLog<'a>> {
             _: '#',
             server_socket: parse_socket,
             _: ']',
+            marker: parse_marker,
             payload: parse_payload
         }
     )
     .parse_next(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Direction;
+
+    fn socket(input: &str) -> Option<Socket<'_>> {
+        parse_socket.parse(input).ok()
+    }
+
+    #[test]
+    fn test_parse_socket() {
+        let ipv4 = socket("117.132.198.154:3649").unwrap();
+        assert_eq!(ipv4.ip(), "117.132.198.154".parse().ok());
+        assert_eq!(ipv4.port, 3649);
+
+        let ipv6 = socket("[::1]:2404").unwrap();
+        assert_eq!(ipv6.host, Host::Ip("::1".parse().unwrap()));
+        assert_eq!(ipv6.to_string(), "[::1]:2404");
+
+        let name = socket("rtu-01.gateway.local:2404").unwrap();
+        assert_eq!(name.host, Host::Name("rtu-01.gateway.local"));
+        assert_eq!(name.ip(), None);
+
+        assert_eq!(socket("999.1.2.3:2404"), None);
+        assert_eq!(socket("1.2.3.4.5:2404"), None);
+        assert_eq!(socket("-rtu:2404"), None);
+        assert_eq!(socket("[::g]:2404"), None);
+        // 端口越界是解析错误而不是 panic
+        assert_eq!(socket("10.0.0.1:70000"), None);
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let mut input =
+            "2024-08-26 00:00:00.720  [[2001:db8::10]:3649#172.19.85.132:5003] R:680407000000";
+        let log = parse_log(&mut input).unwrap();
+        assert_eq!(log.data_time, "2024-08-26 00:00:00.720");
+        assert_eq!(log.client_socket.to_string(), "[2001:db8::10]:3649");
+        assert_eq!(log.server_socket.port, 5003);
+        assert_eq!(log.marker, "R");
+        assert_eq!(log.direction(), Some(Direction::Received));
+        assert_eq!(log.payload, "680407000000");
+
+        // 其它标记照样解析, 只是没有方向
+        let mut input = "2024-08-26 00:00:00.720  [10.0.0.1:3649#172.19.85.132:5003] recv:6804";
+        let log = parse_log(&mut input).unwrap();
+        assert_eq!((log.marker, log.direction()), ("recv", None));
+        assert_eq!(log.payload, "6804");
+
+        let mut input =
+            "2024-08-26 00:00:00.720  [10.0.0.1:70000#172.19.85.132:5003] R:680407000000";
+        assert!(parse_log(&mut input).is_err());
+    }
+}
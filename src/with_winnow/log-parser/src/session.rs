@@ -0,0 +1,572 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::{Apdu, Direction, Iec104Error, IotLog, UFunction};
+
+// 序号为 15 位, 按 32768 取模
+const SEQ_MODULO: u16 = 1 << 15;
+
+// 104 规约的 k/w/t1 参数, 默认值取自 IEC 60870-5-104
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+    // 未被确认的 I 帧最大数目
+    pub k: usize,
+    // 最迟在收到 w 个 I 帧后确认
+    pub w: usize,
+    // TESTFR act 等待 con 的超时
+    pub t1: TimeDelta,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            k: 12,
+            w: 8,
+            t1: TimeDelta::seconds(15),
+        }
+    }
+}
+
+// 会话由客户端和服务端的 Socket 唯一确定
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SessionKey {
+    pub client: String,
+    pub server: String,
+}
+
+impl fmt::Display for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}", self.client, self.server)
+    }
+}
+
+// 帧的发送方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Side {
+    Client,
+    Server,
+}
+
+impl Side {
+    fn peer(self) -> Side {
+        match self {
+            Side::Client => Side::Server,
+            Side::Server => Side::Client,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl From<Direction> for Side {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Received => Side::Client,
+            Direction::Sent => Side::Server,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Client => write!(f, "client"),
+            Side::Server => write!(f, "server"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    // side 发出的 N(S) 跳号, 中间 missing 个 I 帧没有出现在日志中
+    SequenceGap {
+        expected: u16,
+        got: u16,
+        missing: u16,
+    },
+    // side 重发了已经发过的 N(S)
+    Duplicate {
+        expected: u16,
+        got: u16,
+    },
+    // side 发出的 N(R) 确认了对端没有发过的帧
+    InvalidAck {
+        recv_seq: u16,
+    },
+    // side 未被确认的 I 帧超过 k
+    WindowExceeded {
+        outstanding: usize,
+    },
+    // side 收到 w 个以上 I 帧仍未确认
+    AckOverdue {
+        unacknowledged: usize,
+    },
+    // side 发出 TESTFR act 后 t1 内没有收到 con
+    TestFrTimeout {
+        waited: TimeDelta,
+    },
+    // side 在链路已启动时再次发出 STARTDT act, 序号重新从 0 开始
+    Restart,
+    // side 发出了 STOPDT act
+    Stop,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::SequenceGap {
+                expected,
+                got,
+                missing,
+            } => write!(
+                f,
+                "N(S) gap: expected {expected}, got {got} ({missing} missing)"
+            ),
+            EventKind::Duplicate { expected, got } => {
+                write!(f, "duplicate N(S): expected {expected}, got {got}")
+            }
+            EventKind::InvalidAck { recv_seq } => {
+                write!(f, "N(R) {recv_seq} acknowledges unsent frames")
+            }
+            EventKind::WindowExceeded { outstanding } => {
+                write!(f, "{outstanding} unacknowledged I frames exceed k")
+            }
+            EventKind::AckOverdue { unacknowledged } => {
+                write!(
+                    f,
+                    "{unacknowledged} received I frames not acknowledged within w"
+                )
+            }
+            EventKind::TestFrTimeout { waited } => write!(
+                f,
+                "TESTFR con not received after {:.3}s",
+                waited.num_milliseconds() as f64 / 1000.0
+            ),
+            EventKind::Restart => write!(f, "link restarted by STARTDT act"),
+            EventKind::Stop => write!(f, "link stopped by STOPDT act"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEvent {
+    pub time: NaiveDateTime,
+    pub session: SessionKey,
+    pub side: Side,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub first_seen: Option<NaiveDateTime>,
+    pub last_seen: Option<NaiveDateTime>,
+    pub i_frames: usize,
+    pub s_frames: usize,
+    pub u_frames: usize,
+    pub gaps: usize,
+    pub duplicates: usize,
+    pub restarts: usize,
+    pub testfr_timeouts: usize,
+    // 被确认的 I 帧数及其等待确认的总时长和最长时长
+    pub acknowledged: usize,
+    pub total_ack_delay: TimeDelta,
+    pub max_ack_delay: TimeDelta,
+}
+
+impl SessionStats {
+    pub fn mean_ack_delay(&self) -> Option<TimeDelta> {
+        (self.acknowledged > 0).then(|| self.total_ack_delay / self.acknowledged as i32)
+    }
+}
+
+// 一个方向 (一个发送方) 的链路状态
+#[derive(Debug, Default)]
+struct Endpoint {
+    // 下一个期望的 N(S), 会话中途开始记录时为 None
+    next_send: Option<u16>,
+    // 已发出但未被对端确认的 I 帧
+    pending: VecDeque<(u16, NaiveDateTime)>,
+    testfr_since: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    endpoints: [Endpoint; 2],
+    started: bool,
+    stats: SessionStats,
+}
+
+// 返回 seq 是否在 ack 之前, 即已被 N(R) = ack 确认
+fn acknowledged_by(seq: u16, ack: u16) -> bool {
+    let distance = ack.wrapping_sub(seq) % SEQ_MODULO;
+    distance != 0 && distance <= SEQ_MODULO / 2
+}
+
+impl Session {
+    fn on_send_seq(&mut self, side: Side, send_seq: u16, time: NaiveDateTime) -> Option<EventKind> {
+        let endpoint = &mut self.endpoints[side.index()];
+        let event = endpoint.next_send.and_then(|expected| {
+            let ahead = send_seq.wrapping_sub(expected) % SEQ_MODULO;
+            if ahead == 0 {
+                None
+            } else if ahead < SEQ_MODULO / 2 {
+                self.stats.gaps += 1;
+                Some(EventKind::SequenceGap {
+                    expected,
+                    got: send_seq,
+                    missing: ahead,
+                })
+            } else {
+                self.stats.duplicates += 1;
+                Some(EventKind::Duplicate {
+                    expected,
+                    got: send_seq,
+                })
+            }
+        });
+        if !matches!(event, Some(EventKind::Duplicate { .. })) {
+            endpoint.next_send = Some((send_seq + 1) % SEQ_MODULO);
+            endpoint.pending.push_back((send_seq, time));
+        }
+        event
+    }
+
+    // side 发出 N(R), 确认对端的 I 帧
+    fn on_recv_seq(&mut self, side: Side, recv_seq: u16, time: NaiveDateTime) -> Option<EventKind> {
+        let peer = &mut self.endpoints[side.peer().index()];
+        while let Some(&(seq, sent)) = peer.pending.front() {
+            if !acknowledged_by(seq, recv_seq) {
+                break;
+            }
+            peer.pending.pop_front();
+            let delay = time - sent;
+            self.stats.acknowledged += 1;
+            self.stats.total_ack_delay += delay;
+            self.stats.max_ack_delay = self.stats.max_ack_delay.max(delay);
+        }
+        // 对端还没有发出 N(R) - 1 号帧
+        let ahead = peer
+            .next_send
+            .is_some_and(|next| acknowledged_by(next, recv_seq));
+        ahead.then_some(EventKind::InvalidAck { recv_seq })
+    }
+
+    fn restart(&mut self) {
+        for endpoint in &mut self.endpoints {
+            endpoint.next_send = Some(0);
+            endpoint.pending.clear();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SessionAnalyzer {
+    config: SessionConfig,
+    sessions: BTreeMap<SessionKey, Session>,
+    events: Vec<SessionEvent>,
+}
+
+pub fn parse_log_time(data_time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(data_time, "%Y-%m-%d %H:%M:%S%.f").ok()
+}
+
+impl SessionAnalyzer {
+    pub fn new(config: SessionConfig) -> Self {
+        SessionAnalyzer {
+            config,
+            ..Default::default()
+        }
+    }
+
+    // 日志需按时间顺序传入, 时间或 payload 无法解析时返回错误
+    pub fn observe(&mut self, log: &IotLog) -> Result<(), Iec104Error> {
+        let time = parse_log_time(log.data_time)
+            .ok_or_else(|| Iec104Error::InvalidTime(log.data_time.to_string()))?;
+        let apdus = log.apdus()?;
+        let key = SessionKey {
+            client: log.client_socket.to_string(),
+            server: log.server_socket.to_string(),
+        };
+        let side = log
+            .direction()
+            .map(Side::from)
+            .ok_or_else(|| Iec104Error::UnknownDirection(log.marker.to_string()))?;
+        for apdu in &apdus {
+            self.on_apdu(&key, side, time, apdu);
+        }
+        Ok(())
+    }
+
+    fn on_apdu(&mut self, key: &SessionKey, side: Side, time: NaiveDateTime, apdu: &Apdu) {
+        let config = self.config;
+        let session = self.sessions.entry(key.clone()).or_default();
+        let stats = &mut session.stats;
+        stats.first_seen.get_or_insert(time);
+        stats.last_seen = Some(time);
+
+        let mut kinds = Vec::new();
+        // 任意一帧到达时, 检查双方是否有超时的 TESTFR
+        for waiting in [Side::Client, Side::Server] {
+            let endpoint = &mut session.endpoints[waiting.index()];
+            let Some(since) = endpoint.testfr_since else {
+                continue;
+            };
+            let answered = waiting != side && *apdu == Apdu::U(UFunction::TestFrCon);
+            if time - since > config.t1 {
+                endpoint.testfr_since = None;
+                session.stats.testfr_timeouts += 1;
+                kinds.push((
+                    waiting,
+                    EventKind::TestFrTimeout {
+                        waited: time - since,
+                    },
+                ));
+            } else if answered {
+                endpoint.testfr_since = None;
+            }
+        }
+
+        match *apdu {
+            Apdu::I {
+                send_seq, recv_seq, ..
+            } => {
+                session.stats.i_frames += 1;
+                session.started = true;
+                kinds.extend(
+                    session
+                        .on_send_seq(side, send_seq, time)
+                        .map(|kind| (side, kind)),
+                );
+                kinds.extend(
+                    session
+                        .on_recv_seq(side, recv_seq, time)
+                        .map(|kind| (side, kind)),
+                );
+
+                let outstanding = session.endpoints[side.index()].pending.len();
+                if outstanding == config.k + 1 {
+                    kinds.push((side, EventKind::WindowExceeded { outstanding }));
+                }
+                if outstanding == config.w + 1 {
+                    kinds.push((
+                        side.peer(),
+                        EventKind::AckOverdue {
+                            unacknowledged: outstanding,
+                        },
+                    ));
+                }
+            }
+            Apdu::S { recv_seq } => {
+                session.stats.s_frames += 1;
+                kinds.extend(
+                    session
+                        .on_recv_seq(side, recv_seq, time)
+                        .map(|kind| (side, kind)),
+                );
+            }
+            Apdu::U(function) => {
+                session.stats.u_frames += 1;
+                match function {
+                    UFunction::StartDtAct => {
+                        if session.started {
+                            session.stats.restarts += 1;
+                            kinds.push((side, EventKind::Restart));
+                        }
+                        session.started = true;
+                        session.restart();
+                    }
+                    UFunction::StopDtAct => {
+                        session.started = false;
+                        kinds.push((side, EventKind::Stop));
+                    }
+                    UFunction::TestFrAct => {
+                        session.endpoints[side.index()]
+                            .testfr_since
+                            .get_or_insert(time);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.events
+            .extend(kinds.into_iter().map(|(side, kind)| SessionEvent {
+                time,
+                session: key.clone(),
+                side,
+                kind,
+            }));
+    }
+
+    pub fn events(&self) -> &[SessionEvent] {
+        &self.events
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = (&SessionKey, &SessionStats)> {
+        self.sessions
+            .iter()
+            .map(|(key, session)| (key, &session.stats))
+    }
+
+    // 日志结束时仍在等待 con 且已超过 t1 的 TESTFR 记为超时
+    pub fn finish(&mut self, end: NaiveDateTime) {
+        let t1 = self.config.t1;
+        for (key, session) in &mut self.sessions {
+            for side in [Side::Client, Side::Server] {
+                let endpoint = &mut session.endpoints[side.index()];
+                let Some(since) = endpoint.testfr_since else {
+                    continue;
+                };
+                if end - since > t1 {
+                    endpoint.testfr_since = None;
+                    session.stats.testfr_timeouts += 1;
+                    self.events.push(SessionEvent {
+                        time: end,
+                        session: key.clone(),
+                        side,
+                        kind: EventKind::TestFrTimeout {
+                            waited: end - since,
+                        },
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn analyze(lines: &str) -> SessionAnalyzer {
+        let mut analyzer = SessionAnalyzer::new(SessionConfig {
+            k: 3,
+            w: 2,
+            ..Default::default()
+        });
+        for mut line in lines.lines() {
+            let log = parse_log(&mut line).unwrap();
+            analyzer.observe(&log).unwrap();
+        }
+        analyzer
+    }
+
+    #[test]
+    fn test_sequence() {
+        // I 帧 N(S) = 0, 1, 3, 3; 服务端 S 帧确认到 N(R) = 2
+        let analyzer = analyze(
+            "2024-08-26 00:00:00.000  [10.0.0.1:3649#10.0.0.2:2404] R:680407000000
+2024-08-26 00:00:00.100  [10.0.0.1:3649#10.0.0.2:2404] S:68040b000000
+2024-08-26 00:00:01.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812000000000d0103000300c042000000000000
+2024-08-26 00:00:02.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812020000000d0103000300c042000000000000
+2024-08-26 00:00:02.500  [10.0.0.1:3649#10.0.0.2:2404] S:680401000400
+2024-08-26 00:00:03.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812060000000d0103000300c042000000000000
+2024-08-26 00:00:04.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812060000000d0103000300c042000000000000",
+        );
+
+        let kinds: Vec<_> = analyzer
+            .events()
+            .iter()
+            .map(|e| (e.side, &e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (
+                    Side::Client,
+                    &EventKind::SequenceGap {
+                        expected: 2,
+                        got: 3,
+                        missing: 1
+                    }
+                ),
+                (
+                    Side::Client,
+                    &EventKind::Duplicate {
+                        expected: 4,
+                        got: 3
+                    }
+                ),
+            ]
+        );
+
+        let (key, stats) = analyzer.sessions().next().unwrap();
+        assert_eq!(key.to_string(), "10.0.0.1:3649#10.0.0.2:2404");
+        assert_eq!((stats.i_frames, stats.s_frames, stats.u_frames), (4, 1, 2));
+        assert_eq!(stats.acknowledged, 2);
+        assert_eq!(stats.max_ack_delay, TimeDelta::milliseconds(1500));
+        assert_eq!(stats.mean_ack_delay(), Some(TimeDelta::milliseconds(1000)));
+    }
+
+    #[test]
+    fn test_window_and_restart() {
+        let analyzer = analyze(
+            "2024-08-26 00:00:00.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812000000000d0103000300c042000000000000
+2024-08-26 00:00:01.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812020000000d0103000300c042000000000000
+2024-08-26 00:00:02.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812040000000d0103000300c042000000000000
+2024-08-26 00:00:03.000  [10.0.0.1:3649#10.0.0.2:2404] R:6812060000000d0103000300c042000000000000
+2024-08-26 00:00:04.000  [10.0.0.1:3649#10.0.0.2:2404] R:680407000000",
+        );
+
+        let kinds: Vec<_> = analyzer
+            .events()
+            .iter()
+            .map(|e| (e.side, &e.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (Side::Server, &EventKind::AckOverdue { unacknowledged: 3 }),
+                (Side::Client, &EventKind::WindowExceeded { outstanding: 4 }),
+                (Side::Client, &EventKind::Restart),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_testfr_timeout() {
+        let mut analyzer = analyze(
+            "2024-08-26 00:00:00.000  [10.0.0.1:3649#10.0.0.2:2404] S:680443000000
+2024-08-26 00:00:01.000  [10.0.0.1:3649#10.0.0.2:2404] R:680483000000
+2024-08-26 00:00:10.000  [10.0.0.1:3649#10.0.0.2:2404] S:680443000000
+2024-08-26 00:00:30.000  [10.0.0.1:3649#10.0.0.2:2404] R:680483000000
+2024-08-26 00:01:00.000  [10.0.0.1:3649#10.0.0.2:2404] R:680443000000",
+        );
+        analyzer.finish(parse_log_time("2024-08-26 00:02:00.000").unwrap());
+
+        let timeouts: Vec<_> = analyzer
+            .events()
+            .iter()
+            .map(|e| (e.side, e.kind.to_string()))
+            .collect();
+        assert_eq!(
+            timeouts,
+            [
+                (
+                    Side::Server,
+                    "TESTFR con not received after 20.000s".to_string()
+                ),
+                (
+                    Side::Client,
+                    "TESTFR con not received after 60.000s".to_string()
+                ),
+            ]
+        );
+        assert_eq!(analyzer.sessions().next().unwrap().1.testfr_timeouts, 2);
+    }
+
+    #[test]
+    fn test_unknown_direction() {
+        let mut line = "2024-08-26 00:00:00.000  [10.0.0.1:3649#10.0.0.2:2404] X:680407000000";
+        let log = parse_log(&mut line).unwrap();
+        assert_eq!(
+            SessionAnalyzer::default().observe(&log),
+            Err(Iec104Error::UnknownDirection("X".into()))
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+
+use crate::{Direction, Iec104Error, IotLog, SessionKey, parse_log_time};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    pub messages: usize,
+    pub received: usize,
+    pub sent: usize,
+    // payload 十六进制解码后的字节数
+    pub payload_bytes: usize,
+    pub first_seen: Option<NaiveDateTime>,
+    pub last_seen: Option<NaiveDateTime>,
+}
+
+impl TrafficStats {
+    // 没有方向标记的报文只计入 messages
+    fn add(&mut self, time: NaiveDateTime, direction: Option<Direction>, bytes: usize) {
+        self.messages += 1;
+        match direction {
+            Some(Direction::Received) => self.received += 1,
+            Some(Direction::Sent) => self.sent += 1,
+            None => {}
+        }
+        self.payload_bytes += bytes;
+        self.first_seen = Some(self.first_seen.map_or(time, |first| first.min(time)));
+        self.last_seen = Some(self.last_seen.map_or(time, |last| last.max(time)));
+    }
+}
+
+// 按 (时间桶, 客户端#服务端) 统计报文数和字节数, 同时按客户端主机汇总
+#[derive(Debug, Default)]
+pub struct TrafficSummary {
+    // None 表示整个日志为一个桶
+    bucket: Option<TimeDelta>,
+    rows: BTreeMap<(Option<NaiveDateTime>, SessionKey), TrafficStats>,
+    // 客户端重连会换端口, 按主机汇总才能找出最繁忙的 RTU
+    peers: BTreeMap<String, TrafficStats>,
+}
+
+impl TrafficSummary {
+    pub fn new(bucket: Option<TimeDelta>) -> Self {
+        TrafficSummary {
+            bucket,
+            ..Default::default()
+        }
+    }
+
+    // 向下取整到桶的起点
+    fn bucket_start(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let size = self.bucket?.num_seconds().max(1);
+        let secs = time.and_utc().timestamp();
+        DateTime::from_timestamp(secs - secs.rem_euclid(size), 0).map(|start| start.naive_utc())
+    }
+
+    pub fn observe(&mut self, log: &IotLog) -> Result<(), Iec104Error> {
+        let time = parse_log_time(log.data_time)
+            .ok_or_else(|| Iec104Error::InvalidTime(log.data_time.to_string()))?;
+        let bytes = log.payload.trim().len() / 2;
+        let key = SessionKey {
+            client: log.client_socket.to_string(),
+            server: log.server_socket.to_string(),
+        };
+
+        self.rows
+            .entry((self.bucket_start(time), key))
+            .or_default()
+            .add(time, log.direction(), bytes);
+        self.peers
+            .entry(log.client_socket.host.to_string())
+            .or_default()
+            .add(time, log.direction(), bytes);
+        Ok(())
+    }
+
+    // 按时间桶升序, 同一个桶内按会话排序
+    pub fn rows(
+        &self,
+    ) -> impl Iterator<Item = (Option<NaiveDateTime>, &SessionKey, &TrafficStats)> {
+        self.rows
+            .iter()
+            .map(|((bucket, key), stats)| (*bucket, key, stats))
+    }
+
+    // 报文最多的 n 个客户端主机, 数目相同时按字节数
+    pub fn busiest(&self, n: usize) -> Vec<(&str, &TrafficStats)> {
+        let mut peers: Vec<(&str, &TrafficStats)> = self
+            .peers
+            .iter()
+            .map(|(host, stats)| (host.as_str(), stats))
+            .collect();
+        peers.sort_by(|(_, a), (_, b)| {
+            (b.messages, b.payload_bytes).cmp(&(a.messages, a.payload_bytes))
+        });
+        peers.truncate(n);
+        peers
+    }
+
+    // 用 `\t` 分隔, 由调用方对齐 (例如 TabWriter)
+    pub fn write_table(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "BUCKET\tCLIENT\tSERVER\tMESSAGES\tR\tS\tBYTES\tFIRST SEEN\tLAST SEEN"
+        )?;
+        for (bucket, key, stats) in self.rows() {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}",
+                format_time(bucket),
+                key.client,
+                key.server,
+                format_stats(stats, '\t')
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "bucket,client,server,messages,received,sent,payload_bytes,first_seen,last_seen"
+        )?;
+        for (bucket, key, stats) in self.rows() {
+            writeln!(
+                w,
+                "{},{},{},{}",
+                format_time(bucket),
+                key.client,
+                key.server,
+                format_stats(stats, ',')
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_busiest_table(&self, n: usize, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "CLIENT\tMESSAGES\tR\tS\tBYTES\tFIRST SEEN\tLAST SEEN")?;
+        for (host, stats) in self.busiest(n) {
+            writeln!(w, "{host}\t{}", format_stats(stats, '\t'))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_busiest_csv(&self, n: usize, w: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "client,messages,received,sent,payload_bytes,first_seen,last_seen"
+        )?;
+        for (host, stats) in self.busiest(n) {
+            writeln!(w, "{host},{}", format_stats(stats, ','))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_time(time: Option<NaiveDateTime>) -> String {
+    time.map(|time| time.format(TIME_FORMAT).to_string())
+        .unwrap_or_default()
+}
+
+fn format_stats(stats: &TrafficStats, sep: char) -> String {
+    format!(
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+        stats.messages,
+        stats.received,
+        stats.sent,
+        stats.payload_bytes,
+        format_time(stats.first_seen),
+        format_time(stats.last_seen)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    const INPUT: &str = "2024-08-26 00:00:00.720  [117.132.198.154:3649#172.19.85.132:5003] R:68125e48a4000d0103000300c042009cc4de4200
+2024-08-26 00:00:30.000  [117.132.198.154:3649#172.19.85.132:5003] S:680401000a00
+2024-08-26 00:01:10.000  [117.132.198.154:3649#172.19.85.132:5003] R:680443000000
+2024-08-26 00:01:20.000  [117.132.198.154:4100#172.19.85.132:5003] R:680443000000
+2024-08-26 00:01:30.000  [[2001:db8::10]:3649#172.19.85.132:5003] R:680443000000";
+
+    fn summary(bucket: Option<TimeDelta>) -> TrafficSummary {
+        let mut summary = TrafficSummary::new(bucket);
+        for mut line in INPUT.lines() {
+            summary.observe(&parse_log(&mut line).unwrap()).unwrap();
+        }
+        summary
+    }
+
+    #[test]
+    fn test_buckets() {
+        let summary = summary(Some(TimeDelta::minutes(1)));
+        let rows: Vec<_> = summary
+            .rows()
+            .map(|(bucket, key, stats)| (format_time(bucket), key.client.as_str(), stats.messages))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "2024-08-26 00:00:00.000".to_string(),
+                    "117.132.198.154:3649",
+                    2
+                ),
+                (
+                    "2024-08-26 00:01:00.000".to_string(),
+                    "117.132.198.154:3649",
+                    1
+                ),
+                (
+                    "2024-08-26 00:01:00.000".to_string(),
+                    "117.132.198.154:4100",
+                    1
+                ),
+                (
+                    "2024-08-26 00:01:00.000".to_string(),
+                    "[2001:db8::10]:3649",
+                    1
+                ),
+            ]
+        );
+
+        let (_, _, first) = summary.rows().next().unwrap();
+        assert_eq!(
+            (first.received, first.sent, first.payload_bytes),
+            (1, 1, 26)
+        );
+
+        let mut csv = Vec::new();
+        summary.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv.lines().nth(1),
+            Some(
+                "2024-08-26 00:00:00.000,117.132.198.154:3649,172.19.85.132:5003,2,1,1,26,2024-08-26 00:00:00.720,2024-08-26 00:00:30.000"
+            )
+        );
+    }
+
+    #[test]
+    fn test_busiest() {
+        let summary = summary(None);
+        assert_eq!(summary.rows().count(), 3);
+        assert!(summary.rows().all(|(bucket, _, _)| bucket.is_none()));
+
+        // 两个端口的连接合并到同一个主机
+        let busiest: Vec<_> = summary
+            .busiest(1)
+            .into_iter()
+            .map(|(host, stats)| (host, stats.messages, stats.payload_bytes))
+            .collect();
+        assert_eq!(busiest, [("117.132.198.154", 4, 38)]);
+    }
+}
@@ -1,13 +1,72 @@
-#[derive(Debug, Eq, PartialEq)]
+use std::fmt;
+use std::net::IpAddr;
+
+// IP 地址或主机名
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum Host<'a> {
+    Ip(IpAddr),
+    Name(&'a str),
+}
+
+impl fmt::Display for Host<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Ip(ip) => write!(f, "{ip}"),
+            Host::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Socket<'a> {
-    pub ip_address: &'a str,
+    pub host: Host<'a>,
     pub port: u16,
 }
 
+impl Socket<'_> {
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.host {
+            Host::Ip(ip) => Some(ip),
+            Host::Name(_) => None,
+        }
+    }
+}
+
+// IPv6 地址带方括号, 例如 `[::1]:2404`
+impl fmt::Display for Socket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host {
+            Host::Ip(IpAddr::V6(ip)) => write!(f, "[{ip}]:{}", self.port),
+            host => write!(f, "{host}:{}", self.port),
+        }
+    }
+}
+
+// 由 payload 前的 `R` / `S` 标记得到的方向. 会话分析按 `R` 为客户端发往服务端,
+// `S` 为服务端发往客户端来区分双方, 这是对网关日志的约定而不是 parse_log 的要求
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct IotLog<'a> {
     pub data_time: &'a str,
     pub client_socket: Socket<'a>,
     pub server_socket: Socket<'a>,
+    // `]` 和 `:` 之间的原始标记 (去掉两端空白), parse_log 不限制其内容
+    pub marker: &'a str,
     pub payload: &'a str,
 }
+
+impl IotLog<'_> {
+    // 只认识 `R` 和 `S`, 其它标记没有方向
+    pub fn direction(&self) -> Option<Direction> {
+        match self.marker {
+            "R" => Some(Direction::Received),
+            "S" => Some(Direction::Sent),
+            _ => None,
+        }
+    }
+}
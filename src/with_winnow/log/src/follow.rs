@@ -0,0 +1,172 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// 文件的唯一标识, 用于发现日志轮转 (重命名后新建同名文件)
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// 类似 `tail -F`: 按路径跟踪文件, 轮转后重新打开, 截断后从头读
+#[derive(Debug)]
+pub struct Follower {
+    path: PathBuf,
+    reader: BufReader<File>,
+    id: Option<(u64, u64)>,
+    position: u64,
+    // 还没有换行符的半行
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    // from_end 为 true 时跳过已有内容, 只输出之后追加的行
+    pub fn open(path: &Path, from_end: bool) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let position = if from_end {
+            file.seek(SeekFrom::End(0))?
+        } else {
+            0
+        };
+        Ok(Follower {
+            path: path.to_path_buf(),
+            reader: BufReader::new(file),
+            id: file_id(&metadata),
+            position,
+            partial: Vec::new(),
+        })
+    }
+
+    fn drain(&mut self, f: &mut impl FnMut(&str) -> io::Result<()>) -> io::Result<()> {
+        loop {
+            let n = self.reader.read_until(b'\n', &mut self.partial)?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.position += n as u64;
+            if self.partial.ends_with(b"\n") {
+                // 先取出整行, f 出错时这一行也算已经读过
+                let line = std::mem::take(&mut self.partial);
+                f(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']))?;
+            }
+        }
+    }
+
+    // 把目前已经写完的行逐行交给 f, 不会把整个文件读进内存
+    pub fn read_lines(&mut self, mut f: impl FnMut(&str) -> io::Result<()>) -> io::Result<()> {
+        self.drain(&mut f)?;
+
+        // 轮转过程中文件可能暂时不存在, 下次再检查
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(());
+        };
+        if file_id(&metadata) != self.id {
+            // 旧文件已经读完, 切换到新文件
+            let file = File::open(&self.path)?;
+            self.id = file_id(&file.metadata()?);
+            self.reader = BufReader::new(file);
+            self.position = 0;
+            self.partial.clear();
+            self.drain(&mut f)?;
+        } else if metadata.len() < self.position {
+            self.reader.seek(SeekFrom::Start(0))?;
+            self.position = 0;
+            self.partial.clear();
+            self.drain(&mut f)?;
+        }
+        Ok(())
+    }
+
+    // 不再跟踪时, 把文件末尾没有换行符的最后一行也交给 f
+    pub fn finish(&mut self, mut f: impl FnMut(&str) -> io::Result<()>) -> io::Result<()> {
+        if self.partial.is_empty() {
+            return Ok(());
+        }
+        let line = std::mem::take(&mut self.partial);
+        f(String::from_utf8_lossy(&line).trim_end_matches('\r'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn read(follower: &mut Follower) -> Vec<String> {
+        let mut lines = Vec::new();
+        follower
+            .read_lines(|line| {
+                lines.push(line.to_string());
+                Ok(())
+            })
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn test_follow() {
+        let dir = std::env::temp_dir().join(format!("log-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gateway.log");
+        fs::write(&path, "old\n").unwrap();
+
+        let mut follower = Follower::open(&path, true).unwrap();
+        assert!(read(&mut follower).is_empty());
+
+        // 半行要等写完才输出
+        append(&path, "a\r\nb\nc");
+        assert_eq!(read(&mut follower), ["a", "b"]);
+        append(&path, "c\n");
+        assert_eq!(read(&mut follower), ["cc"]);
+
+        // 截断
+        fs::write(&path, "d\n").unwrap();
+        assert_eq!(read(&mut follower), ["d"]);
+
+        // 轮转: 先读完旧文件剩下的行, 再从新文件开头读
+        append(&path, "e\n");
+        fs::rename(&path, dir.join("gateway.log.1")).unwrap();
+        assert_eq!(read(&mut follower), ["e"]);
+        fs::write(&path, "f\n").unwrap();
+        assert_eq!(read(&mut follower), ["f"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_finish() {
+        let path = std::env::temp_dir().join(format!("log-finish-{}.log", std::process::id()));
+        fs::write(&path, "a\nb").unwrap();
+
+        let mut follower = Follower::open(&path, false).unwrap();
+        assert_eq!(read(&mut follower), ["a"]);
+        let mut lines = Vec::new();
+        for _ in 0..2 {
+            follower
+                .finish(|line| {
+                    lines.push(line.to_string());
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(lines, ["b"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
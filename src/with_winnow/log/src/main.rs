@@ -1,11 +1,291 @@
-use log_parser::parse_log;
+mod follow;
 
-fn main() -> Result<(), winnow::error::ParseError<&'static str, &'static str>> {
-    let mut input = "2024-08-26 00:00:00.720  [117.132.198.154:3649#172.19.85.132:5003] R:68125e48a4000d0103000300c042009cc4de4200";
-    let result = parse_log(&mut input);
-    if let Ok(parsed) = result {
-        println!("{:#?}", parsed);
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use clap::{Parser, ValueEnum};
+use log_parser::{Apdu, IotLog, TrafficSummary, TypeId, parse_log};
+use tabwriter::TabWriter;
+
+use follow::Follower;
+
+// 报文类型过滤: 帧格式 i/s/u, 或 I 帧的类型标识
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameFilter {
+    I,
+    S,
+    U,
+    Type(TypeId),
+}
+
+impl FromStr for FrameFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "i" => Ok(FrameFilter::I),
+            "s" => Ok(FrameFilter::S),
+            "u" => Ok(FrameFilter::U),
+            _ => s.parse().map(FrameFilter::Type),
+        }
+    }
+}
+
+impl FrameFilter {
+    fn matches(&self, apdu: &Apdu) -> bool {
+        match (self, apdu) {
+            (FrameFilter::I, Apdu::I { .. }) => true,
+            (FrameFilter::S, Apdu::S { .. }) => true,
+            (FrameFilter::U, Apdu::U(_)) => true,
+            (FrameFilter::Type(type_id), Apdu::I { asdu, .. }) => asdu.type_id == *type_id,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Csv,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "log")]
+#[command(
+    version,
+    about = "Decode IEC 104 gateway logs, optionally following them like tail -F"
+)]
+struct Cli {
+    /// Gateway log file
+    file: PathBuf,
+
+    /// Keep reading as the file grows, reopening it after rotation or truncation
+    #[arg(short = 'F', long)]
+    follow: bool,
+
+    /// Skip existing content and only print lines appended later
+    #[arg(long)]
+    from_end: bool,
+
+    /// Polling interval in milliseconds when following
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    interval: u64,
+
+    /// Keep lines from this client IP or host name, repeatable
+    #[arg(long = "client", value_name = "HOST", value_parser = parse_client)]
+    clients: Vec<String>,
+
+    /// Keep lines to this server port, repeatable
+    #[arg(long = "server-port", value_name = "PORT")]
+    server_ports: Vec<u16>,
+
+    /// Keep lines with this frame: i, s, u or a type id such as M_ME_NC_1 or 13, repeatable
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    types: Vec<FrameFilter>,
+
+    /// Print message counts and payload bytes per client/server pair instead of frames
+    #[arg(short, long, conflicts_with_all = ["follow", "types"])]
+    summary: bool,
+
+    /// Split the summary into time buckets of this many seconds
+    #[arg(
+        long,
+        value_name = "SECS",
+        requires = "summary",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    bucket: Option<u32>,
+
+    /// Only print the N busiest client hosts in the summary
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "summary",
+        conflicts_with = "bucket"
+    )]
+    top: Option<usize>,
+
+    /// Summary output format
+    #[arg(short, long, value_enum, default_value_t = Format::Table, requires = "summary")]
+    format: Format,
+}
+
+// IP 地址统一成 Host 的显示形式, 例如 `2001:DB8::0010` 为 `2001:db8::10`
+fn parse_client(s: &str) -> Result<String, String> {
+    Ok(s.parse::<IpAddr>()
+        .map_or_else(|_| s.to_string(), |ip| ip.to_string()))
+}
+
+impl Cli {
+    // 按 Host 的显示形式比较, 主机名不区分大小写
+    fn keep_socket(&self, log: &IotLog) -> bool {
+        let keep_client = || {
+            let host = log.client_socket.host.to_string();
+            self.clients
+                .iter()
+                .any(|client| client.eq_ignore_ascii_case(&host))
+        };
+        (self.clients.is_empty() || keep_client())
+            && (self.server_ports.is_empty() || self.server_ports.contains(&log.server_socket.port))
     }
 
+    fn keep_apdus(&self, apdus: &[Apdu]) -> bool {
+        self.types.is_empty()
+            || apdus
+                .iter()
+                .any(|apdu| self.types.iter().any(|filter| filter.matches(apdu)))
+    }
+}
+
+// 每个 APDU 输出一行; 无法解码的 payload 在未按类型过滤时原样输出
+fn print_line(cli: &Cli, line: &str, out: &mut impl Write) -> io::Result<()> {
+    let mut input = line;
+    let Ok(log) = parse_log(&mut input) else {
+        eprintln!("invalid line: {line}");
+        return Ok(());
+    };
+    if !cli.keep_socket(&log) {
+        return Ok(());
+    }
+
+    let prefix = format!(
+        "{}  {}#{} {}",
+        log.data_time, log.client_socket, log.server_socket, log.marker
+    );
+    match log.apdus() {
+        Ok(apdus) if cli.keep_apdus(&apdus) => {
+            for apdu in &apdus {
+                writeln!(out, "{prefix} {apdu}")?;
+            }
+        }
+        Ok(_) => {}
+        Err(err) if cli.types.is_empty() => writeln!(out, "{prefix} {} ({err})", log.payload)?,
+        Err(_) => {}
+    }
     Ok(())
 }
+
+fn summarize(cli: &Cli, out: &mut impl Write) -> io::Result<()> {
+    let mut summary = TrafficSummary::new(cli.bucket.map(|secs| TimeDelta::seconds(secs.into())));
+    let mut follower = Follower::open(&cli.file, cli.from_end)?;
+    let mut observe = |line: &str| {
+        let mut input = line;
+        let Ok(log) = parse_log(&mut input) else {
+            eprintln!("invalid line: {line}");
+            return Ok(());
+        };
+        if !cli.keep_socket(&log) {
+            return Ok(());
+        }
+        if let Err(err) = summary.observe(&log) {
+            eprintln!("{err}: {line}");
+        }
+        Ok(())
+    };
+    follower.read_lines(&mut observe)?;
+    follower.finish(&mut observe)?;
+
+    match (cli.format, cli.top) {
+        (Format::Table, None) => summary.write_table(out),
+        (Format::Csv, None) => summary.write_csv(out),
+        (Format::Table, Some(n)) => summary.write_busiest_table(n, out),
+        (Format::Csv, Some(n)) => summary.write_busiest_csv(n, out),
+    }
+}
+
+fn run(cli: &Cli, out: &mut impl Write) -> io::Result<()> {
+    if cli.summary {
+        return summarize(cli, out);
+    }
+    let mut follower = Follower::open(&cli.file, cli.from_end)?;
+    loop {
+        follower.read_lines(|line| print_line(cli, line, out))?;
+        if !cli.follow {
+            return follower.finish(|line| print_line(cli, line, out));
+        }
+        out.flush()?;
+        thread::sleep(Duration::from_millis(cli.interval));
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let stdout = io::stdout().lock();
+
+    let result = if cli.summary && cli.format == Format::Table {
+        let mut tw = TabWriter::new(stdout).padding(2);
+        run(&cli, &mut tw).and_then(|()| tw.flush())
+    } else {
+        let mut out = io::BufWriter::new(stdout);
+        run(&cli, &mut out).and_then(|()| out.flush())
+    };
+    match result {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("{}: {err}", cli.file.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_line() {
+        let line = "2024-08-26 00:00:00.720  [117.132.198.154:3649#172.19.85.132:5003] R:68125e48a4000d0103000300c042009cc4de4200";
+        let print = |args: &[&str]| {
+            let cli = Cli::parse_from(["log", "gateway.log"].iter().chain(args));
+            let mut out = Vec::new();
+            print_line(&cli, line, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            print(&[]),
+            "2024-08-26 00:00:00.720  117.132.198.154:3649#172.19.85.132:5003 R I(ns=9263, nr=82) M_ME_NC_1 spont ca=3: 17088=111.384\n"
+        );
+        assert_eq!(
+            print(&["--client", "117.132.198.154", "-t", "13"]),
+            print(&[])
+        );
+        assert_eq!(print(&["--client", "117.132.198.1"]), "");
+        assert_eq!(print(&["--server-port", "2404"]), "");
+        assert_eq!(print(&["-t", "s", "-t", "M_SP_NA_1"]), "");
+        assert!(Cli::try_parse_from(["log", "gateway.log", "-t", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_summary_args() {
+        let parse = |args: &[&str]| Cli::try_parse_from(["log", "gateway.log"].iter().chain(args));
+        assert_eq!(parse(&["-s", "--bucket", "60"]).unwrap().bucket, Some(60));
+        for bucket in ["0", "-5", "99999999999"] {
+            assert!(parse(&["-s", "--bucket", bucket]).is_err());
+        }
+        assert!(parse(&["-f", "csv"]).is_err());
+        assert!(parse(&["-s", "-f", "csv"]).is_ok());
+        assert!(parse(&["-s", "--top", "3", "--bucket", "60"]).is_err());
+    }
+
+    #[test]
+    fn test_client_host() {
+        let cli = |client: &str| Cli::parse_from(["log", "gateway.log", "--client", client]);
+        let mut line =
+            "2024-08-26 00:00:00.720  [RTU-01.local:3649#172.19.85.132:5003] R:680443000000";
+        let log = parse_log(&mut line).unwrap();
+        assert!(cli("rtu-01.local").keep_socket(&log));
+        assert!(!cli("rtu-02.local").keep_socket(&log));
+
+        let mut line =
+            "2024-08-26 00:00:00.720  [[2001:db8::10]:3649#172.19.85.132:5003] R:680443000000";
+        let log = parse_log(&mut line).unwrap();
+        assert!(cli("2001:DB8:0::0010").keep_socket(&log));
+    }
+}
@@ -1,5 +1,6 @@
 mod parser;
-mod token;
+mod route;
 
 pub use parser::parse_trips;
-pub use token::*;
+pub use route::*;
+pub use trip_model::*;
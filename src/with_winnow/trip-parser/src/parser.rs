@@ -1,36 +1,52 @@
-use std::collections::HashMap;
-use winnow::ascii::{alphanumeric1, digit1, float, newline, space0, space1};
-use winnow::combinator::{opt, preceded, repeat, separated_pair, seq, terminated};
-use winnow::token::take_until;
+use winnow::ascii::{digit1, float, line_ending, space0, space1};
+use winnow::combinator::{alt, eof, preceded, repeat, separated_pair, seq, terminated};
+use winnow::token::take_till;
 use winnow::{ModalResult, Parser};
 
-use crate::{Coordinate, Destination};
+use crate::{Coordinate, Country, Destination, Itinerary};
 
-fn parse_float<'s>(input: &mut &'s str) -> ModalResult<f64> {
+fn parse_float(input: &mut &str) -> ModalResult<f64> {
     float.parse_next(input)
 }
 
-fn parse_tickets<'s>(input: &mut &'s str) -> ModalResult<u32> {
+fn parse_tickets(input: &mut &str) -> ModalResult<u32> {
     preceded(space0, digit1.try_map(str::parse)).parse_next(input)
 }
 
-fn parse_coordinate<'a>(input: &mut &'a str) -> ModalResult<Coordinate> {
+fn parse_coordinate(input: &mut &str) -> ModalResult<Coordinate> {
     preceded(
         space0,
-        separated_pair(parse_float, ',', parse_float).map(|(lat, lon)| Coordinate { lat, lon }),
+        separated_pair(parse_float, ',', parse_float)
+            .verify_map(|(lat, lon)| Coordinate::new(lat, lon).ok()),
     )
     .parse_next(input)
 }
 
+// 空行和只有空白的行
+fn parse_blank_lines(input: &mut &str) -> ModalResult<()> {
+    repeat(0.., (space0, line_ending)).parse_next(input)
+}
+
+// 国家名独占一行, 可以缩进, 不能包含 `:`
 fn parse_country_name<'a>(input: &mut &'a str) -> ModalResult<&'a str> {
-    terminated(alphanumeric1, newline).parse_next(input)
+    preceded(
+        (parse_blank_lines, space0),
+        terminated(take_till(1.., [':', '\n', '\r']), line_ending),
+    )
+    .map(str::trim)
+    .parse_next(input)
 }
 
+// 目的地名不能跨行
 fn parse_city_name<'a>(input: &mut &'a str) -> ModalResult<&'a str> {
-    preceded(space1, take_until(1.., ':').map(|v: &str| v.trim())).parse_next(input)
+    preceded(
+        space1,
+        take_till(1.., [':', '\n', '\r']).map(|v: &str| v.trim()),
+    )
+    .parse_next(input)
 }
 
-fn parse_destination<'a>(input: &mut &'a str) -> ModalResult<Destination> {
+fn parse_destination(input: &mut &str) -> ModalResult<Destination> {
     seq!(
         Destination {
             name: parse_city_name.map(|x| x.to_string()),
@@ -38,23 +54,25 @@ fn parse_destination<'a>(input: &mut &'a str) -> ModalResult<Destination> {
             coordinate: parse_coordinate,
             _: (space0, ':', space0),
             tickets: parse_tickets,
-            _: opt(newline)
+            _: (space0, alt((line_ending, eof)))
         }
     )
     .parse_next(input)
 }
 
-fn parse_destinations<'a>(input: &mut &'a str) -> ModalResult<HashMap<&'a str, Vec<Destination>>> {
-    let country_name = parse_country_name.parse_next(input)?;
-
-    let destinations = repeat(1.., parse_destination).parse_next(input)?;
-    Ok(std::iter::once((country_name, destinations)).collect())
+fn parse_destinations(input: &mut &str) -> ModalResult<Country> {
+    seq!(Country {
+        name: parse_country_name.map(str::to_string),
+        destinations: repeat(1.., parse_destination),
+    })
+    .parse_next(input)
 }
 
-pub fn parse_trips<'a>(
-    input: &mut &'a str,
-) -> ModalResult<Vec<HashMap<&'a str, Vec<Destination>>>> {
-    repeat(0.., parse_destinations).parse_next(input)
+// 末尾的空行一并消耗, 便于用 `Parser::parse` 解析整个文件
+pub fn parse_trips(input: &mut &str) -> ModalResult<Itinerary> {
+    terminated(repeat(0.., parse_destinations), (parse_blank_lines, space0))
+        .map(|countries| Itinerary { countries })
+        .parse_next(input)
 }
 
 #[cfg(test)]
@@ -103,30 +121,32 @@ mod tests {
         Bergen : 60.388533,5.331856 : 4"#;
 
         let trips = parse_trips(&mut input).unwrap();
-        let mut trip: HashMap<&str, Vec<Destination>> = HashMap::new();
-        trip.insert(
-            "Norway",
-            vec![
-                Destination {
-                    name: "Oslo".to_string(),
-                    coordinate: Coordinate {
-                        lat: 59.914289,
-                        lon: 10.738739,
-                    },
-                    tickets: 2,
-                },
-                Destination {
-                    name: "Bergen".to_string(),
-                    coordinate: Coordinate {
-                        lat: 60.388533,
-                        lon: 5.331856,
-                    },
-                    tickets: 4,
-                },
-            ],
+        assert_eq!(
+            trips,
+            Itinerary {
+                countries: vec![Country {
+                    name: "Norway".to_string(),
+                    destinations: vec![
+                        Destination {
+                            name: "Oslo".to_string(),
+                            coordinate: Coordinate {
+                                lat: 59.914289,
+                                lon: 10.738739,
+                            },
+                            tickets: 2,
+                        },
+                        Destination {
+                            name: "Bergen".to_string(),
+                            coordinate: Coordinate {
+                                lat: 60.388533,
+                                lon: 5.331856,
+                            },
+                            tickets: 4,
+                        },
+                    ],
+                }],
+            }
         );
-
-        assert_eq!(trips, vec![trip]);
     }
 
     #[test]
@@ -139,6 +159,72 @@ mod tests {
         Bergen : 60.388533,5.331856 : 4"#;
 
         let trips = parse_trips(&mut input).unwrap();
-        println!("{:#?}", trips);
+        let names: Vec<Vec<&str>> = trips
+            .countries
+            .iter()
+            .map(|country| {
+                std::iter::once(country.name.as_str())
+                    .chain(country.destinations.iter().map(|d| d.name.as_str()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                vec!["Russia", "Vladivostok", "Ulan Ude"],
+                vec!["Norway", "Oslo", "Bergen"]
+            ]
+        );
+        assert_eq!(input, "");
+    }
+
+    // 两个解析器对同一份行程应得到相同的结果
+    #[test]
+    fn test_same_as_trip_lang() {
+        let inputs = [
+            r#"Russia
+    Vladivostok : 43.131621,131.923828 : 4
+    Ulan Ude : 51.841624,107.608101 : 2
+    Saint Petersburg : 59.939977,30.315785 : 10
+Norway
+    Oslo : 59.914289,10.738739 : 2
+    Bergen : 60.388533,5.331856 : 4
+Ukraine
+    Kiev : 50.456001,30.50384 : 3
+Switzerland
+    Wengen : 46.608265,7.922065 : 3
+    Bern : 46.949076,7.448151 : 1"#,
+            "\nUnited Kingdom\n    London : 51.507351,-0.127758 : 12\n\n  \n  Norway\r\n    Oslo : 59.914289,10.738739 : 2\r\n\n  ",
+            // 没有国家
+            "",
+            "\n  \n\r\n\t",
+            // `:` 两侧的空白可有可无
+            "Norway\n    Oslo :  59.9,10.7:2\n    Bergen:60.388533,5.331856 :4  \n\tTromso\t:\t69.6,18.9\t:\t1",
+        ];
+        for input in inputs {
+            let (rest, expected) = trip_lang::parse_trips(input).unwrap();
+            assert_eq!(rest, "");
+            let mut winnow_input = input;
+            assert_eq!(parse_trips(&mut winnow_input), Ok(expected), "{input}");
+            assert_eq!(winnow_input, rest);
+        }
+
+        // 超出范围或非有限的坐标两边都拒绝
+        for input in [
+            "Norway\n    Oslo : 999,10.7 : 2",
+            "Norway\n    Oslo : 59.9,inf : 2",
+            "Norway\n    Oslo : 999,inf : 2",
+            "Norway\n    Oslo : NaN,10.7 : 2",
+        ] {
+            assert!(
+                !matches!(trip_lang::parse_trips(input), Ok(("", _))),
+                "{input}"
+            );
+            let mut winnow_input = input;
+            assert!(
+                (parse_trips, eof).parse_next(&mut winnow_input).is_err(),
+                "{input}"
+            );
+        }
     }
 }
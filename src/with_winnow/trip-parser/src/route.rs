@@ -0,0 +1,221 @@
+use crate::{Country, Distance, Itinerary};
+
+// 浮点误差以内的改进不算改进, 避免 2-opt 来回翻转
+const EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouteOptions<'a> {
+    pub distance: Distance,
+    // 固定的起点和终点城市, 只对包含该城市的国家生效
+    pub start: Option<&'a str>,
+    pub end: Option<&'a str>,
+}
+
+// 开放路径 (不回到起点) 的总长度
+fn length(route: &[usize], matrix: &[Vec<f64>]) -> f64 {
+    route
+        .windows(2)
+        .fold(0.0, |length, pair| length + matrix[pair[0]][pair[1]])
+}
+
+// 从 start 出发每次去最近的未访问城市, end 留到最后
+fn nearest_neighbour(start: usize, end: Option<usize>, matrix: &[Vec<f64>]) -> Vec<usize> {
+    let n = matrix.len();
+    let mut visited = vec![false; n];
+    let mut route = Vec::with_capacity(n);
+    let mut current = start;
+    visited[start] = true;
+    if let Some(end) = end {
+        visited[end] = true;
+    }
+    route.push(start);
+    while let Some(next) = (0..n)
+        .filter(|&i| !visited[i])
+        .min_by(|&a, &b| matrix[current][a].total_cmp(&matrix[current][b]))
+    {
+        visited[next] = true;
+        route.push(next);
+        current = next;
+    }
+    if let Some(end) = end.filter(|&end| end != start) {
+        route.push(end);
+    }
+    route
+}
+
+// 反转 route[i..=j] 能缩短路径就反转, 直到没有改进; 固定的端点不参与反转
+fn two_opt(route: &mut [usize], fixed_start: bool, fixed_end: bool, matrix: &[Vec<f64>]) {
+    let n = route.len();
+    let first = usize::from(fixed_start);
+    let last = n - usize::from(fixed_end);
+    let cost = |a: Option<usize>, b: Option<usize>| match (a, b) {
+        (Some(a), Some(b)) => matrix[a][b],
+        _ => 0.0,
+    };
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in first..last {
+            for j in i + 1..last {
+                let before = i.checked_sub(1).map(|at| route[at]);
+                let after = route.get(j + 1).copied();
+                let (a, b) = (Some(route[i]), Some(route[j]));
+                let delta = cost(before, b) + cost(a, after) - cost(before, a) - cost(b, after);
+                if delta < -EPSILON {
+                    route[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+}
+
+// 最近邻构造后用 2-opt 改进; 没有固定起点时从每个城市出发各试一次, 取最短的
+pub fn optimize_route(country: &Country, options: &RouteOptions) -> Country {
+    let destinations = &country.destinations;
+    let position =
+        |name: Option<&str>| name.and_then(|name| destinations.iter().position(|d| d.name == name));
+    let start = position(options.start);
+    let end = position(options.end).filter(|&end| Some(end) != start);
+
+    let matrix: Vec<Vec<f64>> = destinations
+        .iter()
+        .map(|a| {
+            destinations
+                .iter()
+                .map(|b| options.distance.between(&a.coordinate, &b.coordinate))
+                .collect()
+        })
+        .collect();
+
+    // 只有终点一个城市时从终点出发
+    let starts: Vec<usize> = match start {
+        Some(start) => vec![start],
+        None if destinations.len() == 1 => vec![0],
+        None => (0..destinations.len())
+            .filter(|&i| Some(i) != end)
+            .collect(),
+    };
+    let best = starts
+        .into_iter()
+        .map(|first| {
+            let mut route = nearest_neighbour(first, end, &matrix);
+            two_opt(&mut route, start.is_some(), end.is_some(), &matrix);
+            route
+        })
+        .min_by(|a, b| length(a, &matrix).total_cmp(&length(b, &matrix)))
+        .unwrap_or_default();
+
+    Country {
+        name: country.name.clone(),
+        destinations: best.into_iter().map(|i| destinations[i].clone()).collect(),
+    }
+}
+
+pub fn optimize_routes(itinerary: &Itinerary, options: &RouteOptions) -> Itinerary {
+    Itinerary {
+        countries: itinerary
+            .countries
+            .iter()
+            .map(|country| optimize_route(country, options))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Coordinate, Destination, parse_trips};
+
+    // 赤道上按经度排列的城市, 最短路线就是按经度顺序
+    fn country(lons: &[f64]) -> Country {
+        Country {
+            name: "Line".to_string(),
+            destinations: lons
+                .iter()
+                .map(|&lon| Destination {
+                    name: format!("{lon}"),
+                    coordinate: Coordinate { lat: 0.0, lon },
+                    tickets: 1,
+                })
+                .collect(),
+        }
+    }
+
+    fn names(country: &Country) -> Vec<&str> {
+        country
+            .destinations
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_optimize_route() {
+        let line = country(&[3.0, 0.0, 4.0, 1.0, 2.0]);
+        let options = RouteOptions::default();
+        let route = optimize_route(&line, &options);
+        assert!(
+            names(&route) == ["0", "1", "2", "3", "4"]
+                || names(&route) == ["4", "3", "2", "1", "0"],
+            "{:?}",
+            names(&route)
+        );
+        assert!(route.path_length(Distance::Haversine) < line.path_length(Distance::Haversine));
+
+        // 固定起点和终点
+        let options = RouteOptions {
+            start: Some("2"),
+            end: Some("3"),
+            ..options
+        };
+        let route = optimize_route(&line, &options);
+        assert_eq!(names(&route), ["2", "1", "0", "4", "3"]);
+        let options = RouteOptions {
+            start: None,
+            end: Some("0"),
+            ..options
+        };
+        assert_eq!(
+            names(&optimize_route(&line, &options)),
+            ["4", "3", "2", "1", "0"]
+        );
+
+        // 不在该国家的城市不起作用, 单个城市原样返回
+        let options = RouteOptions {
+            start: Some("Oslo"),
+            ..options
+        };
+        assert_eq!(names(&optimize_route(&country(&[5.0]), &options)), ["5"]);
+        // 唯一的城市就是终点
+        let options = RouteOptions {
+            start: None,
+            end: Some("5"),
+            ..options
+        };
+        assert_eq!(names(&optimize_route(&country(&[5.0]), &options)), ["5"]);
+    }
+
+    // 重排后的行程可以被 parse_trips 解析回来
+    #[test]
+    fn test_round_trip() {
+        let mut input = r#"Russia
+    Vladivostok : 43.131621,131.923828 : 4
+    Saint Petersburg : 59.939977,30.315785 : 10
+    Ulan Ude : 51.841624,107.608101 : 2
+Switzerland
+    Wengen : 46.608265,7.922065 : 3
+    Bern : 46.949076,7.448151 : 1"#;
+        let itinerary = parse_trips(&mut input).unwrap();
+        let optimized = optimize_routes(&itinerary, &RouteOptions::default());
+        assert_eq!(
+            names(&optimized.countries[0]),
+            ["Vladivostok", "Ulan Ude", "Saint Petersburg"]
+        );
+
+        let text = optimized.to_string();
+        assert!(text.starts_with("Russia\n    Vladivostok : 43.131621,131.923828 : 4\n"));
+        assert_eq!(parse_trips(&mut text.as_str()), Ok(optimized));
+    }
+}
@@ -1,24 +1,144 @@
-use trip_parser::parse_trips;
-use winnow::Parser;
-
-fn main() -> Result<(), winnow::error::ParseError<&'static str, &'static str>> {
-    let input = r#"Russia
-    Vladivostok : 43.131621,131.923828 : 4
-    Ulan Ude : 51.841624,107.608101 : 2
-    Saint Petersburg : 59.939977,30.315785 : 10
-Norway
-    Oslo : 59.914289,10.738739 : 2
-    Bergen : 60.388533,5.331856 : 4
-Ukraine
-    Kiev : 50.456001,30.50384 : 3
-Switzerland
-    Wengen : 46.608265,7.922065 : 3
-    Bern : 46.949076,7.448151 : 1"#;
-
-    match parse_trips.parse_peek(input) {
-        Ok((_, itinerary)) => println!("{:#?}", itinerary),
-        Err(e) => eprintln!("Error parsing itinerary: {}", e),
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use tabwriter::TabWriter;
+use trip_model::cli::{self, Method, stdout_result};
+use trip_parser::{Distance, Itinerary, RouteOptions, optimize_routes, parse_trips};
+use winnow::Parser as _;
+
+#[derive(Parser, Debug)]
+#[command(name = "trip")]
+#[command(version, about = "Parse trip itineraries with winnow")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(flatten)]
+    Shared(cli::Command),
+    /// Reorder each country's destinations into a short route
+    Route {
+        /// Itinerary file, the built-in example when omitted
+        file: Option<PathBuf>,
+
+        /// Distance formula
+        #[arg(short, long, value_enum, default_value_t = Method::Haversine)]
+        method: Method,
+
+        /// Start the route of the country containing this city there
+        #[arg(long, value_name = "CITY")]
+        start: Option<String>,
+
+        /// End the route of the country containing this city there
+        #[arg(long, value_name = "CITY")]
+        end: Option<String>,
+
+        /// Write the reordered itinerary in trip format to this file, `-` for stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+fn load(file: Option<&PathBuf>) -> Result<Itinerary, String> {
+    let input = cli::read(file)?;
+    parse_trips
+        .parse(input.as_str())
+        .map_err(|e| format!("Error parsing itinerary:\n{e}"))
+}
+
+// 每个国家重排前后的路线长度 (千米)
+fn write_route_table(
+    before: &Itinerary,
+    after: &Itinerary,
+    distance: Distance,
+    w: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(w, "COUNTRY\tBEFORE KM\tAFTER KM\tSAVED KM\tROUTE")?;
+    for (old, new) in before.countries.iter().zip(&after.countries) {
+        let (old_length, new_length) = (old.path_length(distance), new.path_length(distance));
+        let route: Vec<&str> = new.destinations.iter().map(|d| d.name.as_str()).collect();
+        writeln!(
+            w,
+            "{}\t{:.1}\t{:.1}\t{:.1}\t{}",
+            old.name,
+            old_length / 1000.0,
+            new_length / 1000.0,
+            (old_length - new_length) / 1000.0,
+            route.join(" -> ")
+        )?;
     }
+    Ok(())
+}
+
+fn route(
+    file: Option<&PathBuf>,
+    distance: Distance,
+    start: Option<&str>,
+    end: Option<&str>,
+    output: Option<&PathBuf>,
+) -> Result<(), String> {
+    let itinerary = load(file)?;
+    for city in [start, end].into_iter().flatten() {
+        if !itinerary.destinations().any(|(_, d)| d.name == city) {
+            return Err(format!("no destination named {city:?}"));
+        }
+    }
+    let options = RouteOptions {
+        distance,
+        start,
+        end,
+    };
+    let optimized = optimize_routes(&itinerary, &options);
+
+    // 行程输出到 stdout 时, 表格改为输出到 stderr
+    let to_stdout = output.is_some_and(|path| path.as_os_str() == "-");
+    let table = |w: &mut dyn Write| -> io::Result<()> {
+        let mut tw = TabWriter::new(w).padding(2);
+        write_route_table(&itinerary, &optimized, distance, &mut tw)?;
+        tw.flush()
+    };
+    if to_stdout {
+        table(&mut io::stderr().lock()).map_err(|e| e.to_string())?;
+        stdout_result(write!(io::stdout().lock(), "{optimized}"))?;
+    } else {
+        stdout_result(table(&mut io::stdout().lock()))?;
+    }
+    if let Some(path) = output.filter(|_| !to_stdout) {
+        fs::write(path, optimized.to_string()).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok(())
+}
 
+fn run(cli: Cli) -> Result<(), String> {
+    match cli
+        .command
+        .unwrap_or(Command::Shared(cli::Command::Show { file: None }))
+    {
+        Command::Shared(command) => cli::run(command, load)?,
+        Command::Route {
+            file,
+            method,
+            start,
+            end,
+            output,
+        } => route(
+            file.as_ref(),
+            method.into(),
+            start.as_deref(),
+            end.as_deref(),
+            output.as_ref(),
+        )?,
+    }
     Ok(())
 }
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}